@@ -0,0 +1,103 @@
+use crate::Taxon;
+use std::error::Error;
+use std::path::Path;
+use turbosql::{execute, select};
+
+// Header names used by the IOC master list, the Clements/eBird checklist and a few
+// common exports, normalised to lower case alphanumerics.
+const COMMON_NAME_HEADERS: &[&str] = &["englishname", "commonname", "english", "speciesenglish", "primarycomname", "iocenglishname"];
+const SCIENTIFIC_NAME_HEADERS: &[&str] = &["scientificname", "speciesscientific", "sciname", "scientific"];
+const GENUS_HEADERS: &[&str] = &["genus"];
+const SPECIES_HEADERS: &[&str] = &["species", "speciesepithet"];
+const FAMILY_HEADERS: &[&str] = &["family", "familyscientific", "familyname"];
+const ORDER_HEADERS: &[&str] = &["order", "order1", "ordername"];
+const CODE_HEADERS: &[&str] = &["speciescode", "code", "ebirdcode", "bandingcode"];
+const CATEGORY_HEADERS: &[&str] = &["category", "rank"];
+
+fn normalise(header: &str) -> String {
+    header.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+fn find_column(headers: &[String], names: &[&str]) -> Option<usize> {
+    names.iter().find_map(|name| headers.iter().position(|header| header == name))
+}
+
+fn field(record: &csv::StringRecord, column: Option<usize>) -> Option<String> {
+    column
+        .and_then(|i| record.get(i))
+        .map(|value| value.trim().to_string())
+        .filter(|value| value != "")
+}
+
+/// Replaces the stored checklist with the species in a CSV file, returning how many were read.
+pub fn import(path: &Path) -> Result<usize, Box<dyn Error>> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_path(path)?;
+    let headers: Vec<String> = reader.headers()?.iter().map(normalise).collect();
+
+    let common_name = find_column(&headers, COMMON_NAME_HEADERS)
+        .ok_or("no English/common name column found")?;
+    let scientific_name = find_column(&headers, SCIENTIFIC_NAME_HEADERS);
+    let genus = find_column(&headers, GENUS_HEADERS);
+    let species = find_column(&headers, SPECIES_HEADERS);
+    let family = find_column(&headers, FAMILY_HEADERS);
+    let order = find_column(&headers, ORDER_HEADERS);
+    let code = find_column(&headers, CODE_HEADERS);
+    let category = find_column(&headers, CATEGORY_HEADERS);
+
+    // A checklist runs to thousands of rows, so write it in one transaction.
+    execute!("BEGIN")?;
+    let result = (|| -> Result<usize, Box<dyn Error>> {
+        execute!("DELETE FROM taxon")?;
+        let mut count = 0;
+        for record in reader.records() {
+            let record = record?;
+            // Clements lists subspecies, hybrids etc. alongside species; only keep the species.
+            if let Some(category) = field(&record, category) {
+                if !category.eq_ignore_ascii_case("species") {
+                    continue;
+                }
+            }
+            // IOC lists orders and families on their own rows, which have no English name.
+            let name = match field(&record, common_name) {
+                Some(name) => name,
+                None => continue,
+            };
+            let scientific = field(&record, scientific_name).or_else(|| {
+                match (field(&record, genus), field(&record, species)) {
+                    (Some(genus), Some(species)) => Some(format!("{} {}", genus, species)),
+                    _ => None,
+                }
+            });
+            Taxon {
+                rowid: None,
+                common_name: Some(name),
+                scientific_name: scientific,
+                family: field(&record, family),
+                taxon_order: field(&record, order),
+                species_code: field(&record, code),
+            }.insert()?;
+            count += 1;
+        }
+        Ok(count)
+    })();
+
+    match result {
+        Ok(count) => {
+            execute!("COMMIT")?;
+            Ok(count)
+        }
+        Err(error) => {
+            execute!("ROLLBACK").ok();
+            Err(error)
+        }
+    }
+}
+
+/// Common names from the imported checklist, for completing species names.
+pub fn common_names() -> Vec<String> {
+    select!(Vec<String> "common_name from taxon order by common_name").unwrap_or_default()
+}
+
+pub fn lookup(common_name: &str) -> Option<Taxon> {
+    select!(Taxon "where common_name = ? collate nocase", common_name).ok()
+}
@@ -0,0 +1,287 @@
+use crate::colors;
+use crate::gui::charts::{export_chart, ChartSettings, ChartType, Granularity, Metric, Range, SiteFilter};
+use crate::reports;
+use crate::{Animal, Observer, Site};
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+use turbosql::select;
+
+const USAGE: &str = "Usage: bird_counter [COMMAND]
+
+Without a command the touch screen interface is started.
+
+Commands:
+  log <species> [--count N]        log a sighting of N birds (default 1)
+  clear <species>                  move today's sightings of a species to the trash
+  species list                     list the species being counted
+  sightings list [FILTERS]         list sightings, oldest first
+  report weekly [FILTERS]          days seen and birds counted per species per week
+  export chart FILE [CHART]        save a chart as a .png, .svg or .pdf file
+  help                             show this message
+
+Filters:
+  --from YYYY-MM-DD  --to YYYY-MM-DD  --site NAME  --observer NAME
+
+Chart options:
+  --width N  --height N            size in pixels (points for SVG and PDF), default 1200x800
+  --type bar|line|stacked|heatmap|pie
+  --metric days|total|max          --by day|week|month|year
+  --range 30days|12months|year|all
+  --site NAME  --observer NAME  --species NAME (for the heatmap)
+
+Sightings are logged against the site and observer last picked in the interface.
+Output is tab separated, with a header line.";
+
+/// Command line arguments split into positional words and `--name value` options.
+struct Args {
+    words: Vec<String>,
+    options: HashMap<String, String>,
+}
+
+impl Args {
+    fn parse(args: &[String]) -> Result<Args, Box<dyn Error>> {
+        let mut words = Vec::new();
+        let mut options = HashMap::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if let Some(name) = arg.strip_prefix("--") {
+                match name.find('=') {
+                    Some(i) => options.insert(name[..i].to_string(), name[i + 1..].to_string()),
+                    None => options.insert(
+                        name.to_string(),
+                        iter.next().ok_or(format!("--{} needs a value", name))?.clone(),
+                    ),
+                };
+            } else {
+                words.push(arg.clone());
+            }
+        }
+        Ok(Args { words, options })
+    }
+
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(|value| value.as_str())
+    }
+
+    /// The positional words joined back up, for species names like "Rainbow Lorikeet".
+    fn species(&self) -> Result<String, Box<dyn Error>> {
+        if self.words.is_empty() {
+            return Err("no species given".into());
+        }
+        Ok(self.words.join(" "))
+    }
+}
+
+/// Runs the command named by the arguments, if any, returning the process exit code.
+/// Returns `None` when there is no command, or only GTK options, and the GUI should start instead.
+pub fn run(args: &[String]) -> Option<i32> {
+    let command = args.get(1)?;
+    let rest = &args[2..];
+    let result = match command.as_str() {
+        "log" | "clear" => {
+            // Logging and clearing are published to MQTT, so wait for that to be sent.
+            crate::mqtt::start();
+            let result = if command == "log" { log(rest) } else { clear(rest) };
+            crate::mqtt::stop();
+            refresh_running_instance();
+            result
+        }
+        "species" => species(rest),
+        "sightings" => sightings(rest),
+        "report" => report(rest),
+        "export" => export(rest),
+        "help" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        // Anything else that looks like an option is left for GTK to handle.
+        _ if command.starts_with('-') => return None,
+        _ => {
+            eprintln!("Unknown command \"{}\"\n\n{}", command, USAGE);
+            return Some(1);
+        }
+    };
+    Some(match result {
+        Ok(()) => 0,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            1
+        }
+    })
+}
+
+/// Gets the touch screen, if it's running, to show a change made from the command line.
+fn refresh_running_instance() {
+    let app = gio::Application::new(Some(crate::APP_ID), gio::ApplicationFlags::empty());
+    crate::activate_remote_action(&app, "refresh", None);
+}
+
+fn subcommand<'a>(args: &'a [String], expected: &str) -> Result<&'a [String], Box<dyn Error>> {
+    match args.first() {
+        Some(word) if word == expected => Ok(&args[1..]),
+        _ => Err("unknown command, try \"bird_counter help\"".into()),
+    }
+}
+
+fn find_site(name: Option<&str>) -> Result<Option<i64>, Box<dyn Error>> {
+    match name {
+        Some(name) => select!(Site "where name = ? collate nocase", name)
+            .map(|site| site.rowid)
+            .map_err(|_| format!("no site called \"{}\"", name).into()),
+        None => Ok(None),
+    }
+}
+
+fn find_observer(name: Option<&str>) -> Result<Option<i64>, Box<dyn Error>> {
+    match name {
+        Some(name) => select!(Observer "where name = ? collate nocase", name)
+            .map(|observer| observer.rowid)
+            .map_err(|_| format!("no observer called \"{}\"", name).into()),
+        None => Ok(None),
+    }
+}
+
+/// The filter options as a report filter; `--to` is inclusive of that day.
+fn filter(args: &Args) -> Result<reports::Filter, Box<dyn Error>> {
+    let mut filter = reports::Filter::default();
+    if let Some(date) = args.option("from") {
+        filter.from = reports::parse_date(date)?.timestamp();
+    }
+    if let Some(date) = args.option("to") {
+        filter.to = (reports::parse_date(date)? + chrono::Duration::days(1)).timestamp();
+    }
+    filter.site_id = find_site(args.option("site"))?;
+    filter.observer_id = find_observer(args.option("observer"))?;
+    Ok(filter)
+}
+
+fn log(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let args = Args::parse(args)?;
+    let animal = reports::find_animal(&args.species()?).and_then(reports::loggable)?;
+    let individuals = match args.option("count") {
+        Some(count) => count.parse::<i64>().map_err(|_| format!("\"{}\" is not a number", count))?,
+        None => 1,
+    };
+    if individuals < 1 {
+        return Err("individuals must be at least 1".into());
+    }
+    crate::log_sighting(animal.rowid.unwrap_or(0), individuals)?;
+    println!("Logged {} × {}", individuals, animal.name.unwrap_or_default());
+    Ok(())
+}
+
+fn clear(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let args = Args::parse(args)?;
+    let animal = reports::find_animal(&args.species()?)?;
+    crate::clear_sighting(animal.rowid.unwrap_or(0))?;
+    println!("Cleared today's sightings of {}", animal.name.unwrap_or_default());
+    Ok(())
+}
+
+fn species(args: &[String]) -> Result<(), Box<dyn Error>> {
+    subcommand(args, "list")?;
+    colors::assign_missing()?;
+    let animals = select!(Vec<Animal> "order by name")?;
+    println!("id\tname\tscientific name\tfamily\tcode\tcolour");
+    for animal in animals {
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            animal.rowid.unwrap_or(0),
+            animal.name.unwrap_or_default(),
+            animal.scientific_name.unwrap_or_default(),
+            animal.family.unwrap_or_default(),
+            animal.species_code.unwrap_or_default(),
+            animal.color.unwrap_or_default(),
+        );
+    }
+    Ok(())
+}
+
+fn sightings(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let args = Args::parse(subcommand(args, "list")?)?;
+    let rows = reports::sightings(&filter(&args)?)?;
+    println!("seen at\tspecies\tindividuals\tsite\tobserver");
+    for row in rows {
+        println!(
+            "{}\t{}\t{}\t{}\t{}",
+            Local.timestamp(row.seen_at.unwrap_or(0), 0).format("%Y-%m-%d %H:%M:%S"),
+            row.species.unwrap_or_default(),
+            row.individuals.unwrap_or(1),
+            row.site.unwrap_or_default(),
+            row.observer.unwrap_or_default(),
+        );
+    }
+    Ok(())
+}
+
+fn report(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let args = Args::parse(subcommand(args, "weekly")?)?;
+    colors::assign_missing()?;
+    let rows = reports::weekly(&filter(&args)?)?;
+    println!("week\tspecies\tcolour\tdays seen\tindividuals");
+    for row in rows {
+        println!(
+            "{}\t{}\t{}\t{}\t{}",
+            row.week.unwrap_or_default(),
+            row.species.unwrap_or_default(),
+            row.color.unwrap_or_default(),
+            row.days.unwrap_or(0),
+            row.individuals.unwrap_or(0),
+        );
+    }
+    Ok(())
+}
+
+/// Looks up a chart option by its id, e.g. `--by week`, as the chart controls name them.
+fn chart_option<T>(args: &Args, name: &str, from_id: fn(&str) -> T, id: fn(&T) -> &'static str) -> Result<Option<T>, Box<dyn Error>> {
+    match args.option(name) {
+        Some(value) => {
+            let option = from_id(value);
+            if id(&option) != value {
+                return Err(format!("\"{}\" isn't a choice for --{}", value, name).into());
+            }
+            Ok(Some(option))
+        }
+        None => Ok(None),
+    }
+}
+
+fn export(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let args = Args::parse(subcommand(args, "chart")?)?;
+    let path = args.words.first().ok_or("no file to save the chart to given")?;
+    let size = |name: &str, default: i32| -> Result<i32, Box<dyn Error>> {
+        match args.option(name) {
+            Some(value) => value.parse::<i32>().ok().filter(|n| *n > 0)
+                .ok_or_else(|| format!("--{} needs a number of pixels", name).into()),
+            None => Ok(default),
+        }
+    };
+    let (width, height) = (size("width", 1200)?, size("height", 800)?);
+    colors::assign_missing()?;
+
+    let mut settings = ChartSettings::default();
+    if let Some(chart_type) = chart_option(&args, "type", ChartType::from_id, ChartType::id)? {
+        settings.chart_type = chart_type;
+    }
+    if let Some(metric) = chart_option(&args, "metric", Metric::from_id, Metric::id)? {
+        settings.metric = metric;
+    }
+    if let Some(granularity) = chart_option(&args, "by", Granularity::from_id, Granularity::id)? {
+        settings.granularity = granularity;
+    }
+    if let Some(range) = chart_option(&args, "range", Range::from_id, Range::id)? {
+        settings.range = range;
+    }
+    if let Some(site_id) = find_site(args.option("site"))? {
+        settings.site_filter = SiteFilter::Only(site_id);
+    }
+    settings.observer_id = find_observer(args.option("observer"))?;
+    if let Some(name) = args.option("species") {
+        settings.species = reports::find_animal(name)?.rowid;
+    }
+    export_chart(&settings, Path::new(path), width, height)?;
+    println!("Saved the chart to {}", path);
+    Ok(())
+}
@@ -0,0 +1,158 @@
+//! Each species' colour, stored with it so that it's drawn the same in every chart.
+
+use crate::Animal;
+use std::error::Error;
+use turbosql::{execute, select};
+
+pub type Rgb = (u8, u8, u8);
+
+/// The LibreOffice chart palette, whose colours are easy to tell apart. Species beyond these
+/// are given hues spread around the colour wheel instead.
+const PALETTE: [Rgb; 12] = [
+    (0x00, 0x45, 0x86),
+    (0xff, 0x42, 0x0e),
+    (0xff, 0xd3, 0x20),
+    (0x57, 0x9d, 0x1c),
+    (0x7e, 0x00, 0x21),
+    (0x83, 0xca, 0xff),
+    (0x31, 0x40, 0x04),
+    (0xae, 0xcf, 0x00),
+    (0x4b, 0x1f, 0x6f),
+    (0xff, 0x95, 0x0e),
+    (0xc5, 0x00, 0x0b),
+    (0x00, 0x84, 0xd1),
+];
+
+/// Shown for a species that hasn't been given a colour yet.
+pub const UNASSIGNED: Rgb = (0x80, 0x80, 0x80);
+
+/// Parses a colour stored as `#rrggbb`.
+pub fn parse(color: &str) -> Option<Rgb> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some(((value >> 16) as u8, (value >> 8) as u8, value as u8))
+}
+
+pub fn to_hex((r, g, b): Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// The colour a species is drawn in.
+pub fn of(animal: &Animal) -> Rgb {
+    animal.color.as_deref().and_then(parse).unwrap_or(UNASSIGNED)
+}
+
+/// Mixes a colour with white, from 0 (unchanged) to 1 (white).
+pub fn lighten((r, g, b): Rgb, amount: f64) -> Rgb {
+    let mix = |c: u8| (c as f64 + (255.0 - c as f64) * amount).round() as u8;
+    (mix(r), mix(g), mix(b))
+}
+
+/// The `n`th colour handed out: the palette, then hues a golden angle (about 137.5°) apart, so
+/// each one lands in the biggest gap left by the ones before.
+fn nth(n: usize) -> Rgb {
+    match PALETTE.get(n) {
+        Some(color) => *color,
+        None => from_hsv(((n - PALETTE.len()) as f64 * 137.508) % 360.0, 0.75, 0.85),
+    }
+}
+
+fn from_hsv(hue: f64, saturation: f64, value: f64) -> Rgb {
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    let byte = |c: f64| ((c + m) * 255.0).round() as u8;
+    (byte(r), byte(g), byte(b))
+}
+
+/// The colours species have been given so far.
+fn taken() -> Result<Vec<Rgb>, Box<dyn Error>> {
+    Ok(select!(Vec<Animal> "where color is not null")?.iter().map(of).collect())
+}
+
+fn first_unused(taken: &[Rgb]) -> Rgb {
+    (0..).map(nth).find(|color| !taken.contains(color)).unwrap_or(UNASSIGNED)
+}
+
+/// The first colour that no species has yet, for a new one.
+pub fn unused() -> Rgb {
+    first_unused(&taken().unwrap_or_default())
+}
+
+pub fn set(animal_id: i64, color: Rgb) -> Result<(), Box<dyn Error>> {
+    execute!("UPDATE animal SET color = ? WHERE rowid = ?", to_hex(color), animal_id)?;
+    Ok(())
+}
+
+/// Gives a colour to each species added before species had them, oldest first. Species are
+/// given one when they're added, so once this has been done it's recorded in the
+/// `colors_assigned` setting and skipped.
+pub fn assign_missing() -> Result<(), Box<dyn Error>> {
+    if crate::get_setting("colors_assigned").is_some() {
+        return Ok(());
+    }
+    let mut taken = taken()?;
+    for animal in select!(Vec<Animal> "where color is null order by rowid")? {
+        if let Some(animal_id) = animal.rowid {
+            let color = first_unused(&taken);
+            set(animal_id, color)?;
+            taken.push(color);
+        }
+    }
+    crate::set_setting("colors_assigned", "1")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_hex_colours() {
+        assert_eq!(parse("#004586"), Some((0x00, 0x45, 0x86)));
+        assert_eq!(parse("#FFd320"), Some((0xff, 0xd3, 0x20)));
+        assert_eq!(parse(&to_hex((0x57, 0x9d, 0x1c))), Some((0x57, 0x9d, 0x1c)));
+        for color in &["004586", "#00458", "#0045866", "#00458g", "#+12345", "", "#"] {
+            assert_eq!(parse(color), None, "{} was parsed", color);
+        }
+    }
+
+    #[test]
+    fn from_hsv_covers_the_colour_wheel() {
+        assert_eq!(from_hsv(0.0, 1.0, 1.0), (255, 0, 0));
+        assert_eq!(from_hsv(60.0, 1.0, 1.0), (255, 255, 0));
+        assert_eq!(from_hsv(120.0, 1.0, 1.0), (0, 255, 0));
+        assert_eq!(from_hsv(240.0, 1.0, 1.0), (0, 0, 255));
+        assert_eq!(from_hsv(300.0, 1.0, 1.0), (255, 0, 255));
+        assert_eq!(from_hsv(0.0, 0.0, 0.5), (128, 128, 128));
+    }
+
+    #[test]
+    fn nth_hands_out_the_palette_then_distinct_hues() {
+        assert_eq!(nth(0), PALETTE[0]);
+        assert_eq!(nth(PALETTE.len() - 1), PALETTE[PALETTE.len() - 1]);
+        assert_eq!(nth(PALETTE.len()), (217, 54, 54));
+        let colors: Vec<Rgb> = (0..100).map(nth).collect();
+        for (i, color) in colors.iter().enumerate() {
+            assert!(!colors[..i].contains(color), "colour {} repeats {:?}", i, color);
+        }
+    }
+
+    #[test]
+    fn first_unused_skips_taken_colours() {
+        assert_eq!(first_unused(&[]), PALETTE[0]);
+        assert_eq!(first_unused(&[PALETTE[0], PALETTE[2]]), PALETTE[1]);
+        assert_eq!(first_unused(&PALETTE), nth(PALETTE.len()));
+    }
+}
@@ -1,49 +1,114 @@
 extern crate gtk;
 
+pub mod actions;
+pub mod cache;
 pub mod charts;
 pub mod images;
+pub mod named;
+pub mod notices;
+pub mod observers;
+pub mod sites;
+pub mod species;
+pub mod toast;
+pub mod trash;
 
-use crate::Animal;
 use directories_next::ProjectDirs;
 use gdk::prelude::*;
 use gtk::prelude::*;
 use gtk::{
-    Box, DrawingArea, GesturePan, GestureZoom, MessageDialog, Orientation,
-    Stack, Window,
+    Box, Button, ComboBoxText, Dialog, Entry, FileChooserDialog, FileFilter, GesturePan, GestureZoom,
+    Label, ListBox, MessageDialog, Orientation, Overlay, ResponseType, SpinButton, Stack, Window,
 };
+use std::cell::Cell;
 use std::path::Path;
 use std::path::PathBuf;
+use std::rc::Rc;
 
+#[derive(Clone)]
 pub struct Gui {
     pub window: gtk::ApplicationWindow,
+    pub stack: gtk::Stack,
+    pub toast: toast::Toast,
+    pub notices: notices::Notices,
+    pub toolbar: gtk::Box,
     pub images: gtk::Box,
     pub charts: gtk::Box,
-    pub drawing_area: gtk::DrawingArea,
+    pub chart: charts::ChartView,
+    pub site_filter: gtk::ComboBoxText,
+    pub observer_filter: gtk::ComboBoxText,
+    pub chart_species: gtk::ComboBoxText,
+    pub trash: gtk::ListBox,
+    pub species: gtk::ListBox,
+    pub page_label: gtk::Label,
+    pub previous_page: gtk::Button,
+    pub next_page: gtk::Button,
+    pub page: Rc<Cell<usize>>,
+    pub grid_size: Rc<Cell<(usize, usize)>>,
+    /// Set while the chart's combos are refilled, so they don't redraw the chart as they go.
+    pub populating: Rc<Cell<bool>>,
 }
 
 impl Gui {
     pub fn new(window: gtk::ApplicationWindow) -> Gui {
         let g = Gui {
             window,
+            stack: Stack::new(),
+            toast: toast::Toast::new(),
+            notices: notices::Notices::new(),
+            toolbar: Box::new(Orientation::Horizontal, 5),
             images: Box::new(Orientation::Vertical, 5),
-            charts: Box::new(Orientation::Horizontal, 5),
-            drawing_area: DrawingArea::new(),
+            charts: Box::new(Orientation::Vertical, 5),
+            chart: charts::ChartView::new(),
+            site_filter: ComboBoxText::new(),
+            observer_filter: ComboBoxText::new(),
+            chart_species: ComboBoxText::new(),
+            trash: ListBox::new(),
+            species: ListBox::new(),
+            page_label: Label::new(None),
+            previous_page: Button::with_label("◀"),
+            next_page: Button::with_label("▶"),
+            page: Rc::new(Cell::new(0)),
+            grid_size: Rc::new(Cell::new(images::grid_size(0, 0))),
+            populating: Rc::new(Cell::new(false)),
         };
 
         g.images.set_homogeneous(true);
-        g.charts.set_homogeneous(true);
 
         g
     }
 
-    pub fn build(&self, animals: Vec<Animal>) -> gtk::Widget {
-        let stack = Stack::new();
+    pub fn build(&self) -> gtk::Widget {
+        let stack = self.stack.clone();
 
-        charts::update_chart(&self.drawing_area);
-        self.charts.add(&self.drawing_area);
+        self.chart.update();
+        self.charts.pack_start(&self.chart_controls(), false, false, 0);
+        self.charts.pack_start(&self.chart.widget, true, true, 0);
 
-        stack.add_named(&self.images, "birds");
+        self.toolbar.pack_end(&self.named_picker(&sites::SITES), false, false, 0);
+        self.toolbar.pack_end(&self.named_picker(&observers::OBSERVERS), false, false, 0);
+        let trash = Button::with_label("Trash");
+        let gui = self.clone();
+        trash.connect_clicked(move |_| gui.show_trash());
+        self.toolbar.pack_start(&trash, false, false, 0);
+        let species = Button::with_label("Species");
+        let gui = self.clone();
+        species.connect_clicked(move |_| gui.show_species());
+        self.toolbar.pack_start(&species, false, false, 0);
+        let gui = self.clone();
+        self.previous_page.connect_clicked(move |_| gui.turn_page(-1));
+        let gui = self.clone();
+        self.next_page.connect_clicked(move |_| gui.turn_page(1));
+        self.toolbar.pack_start(&self.previous_page, false, false, 0);
+        self.toolbar.pack_start(&self.page_label, false, false, 0);
+        self.toolbar.pack_start(&self.next_page, false, false, 0);
+        let birds = Box::new(Orientation::Vertical, 5);
+        birds.pack_start(&self.toolbar, false, false, 0);
+        birds.pack_start(&self.images, true, true, 0);
+
+        stack.add_named(&birds, "birds");
         stack.add_named(&self.charts, "charts");
+        stack.add_named(&self.build_trash(), "trash");
+        stack.add_named(&self.build_species(), "species");
         stack.set_homogeneous(true);
         stack.set_transition_type(gtk::StackTransitionType::SlideLeft);
 
@@ -86,28 +151,164 @@ impl Gui {
             stack.set_data("zoom_gesture", zoom);
         }
 
-        self.load_images(&animals);
+        self.load_images();
+        // Re-lay the tiles once the window has settled on a new size.
+        let gui = self.clone();
+        let allocated = Cell::new((0, 0));
+        self.images.connect_size_allocate(move |_, allocation| {
+            let size = (allocation.width, allocation.height);
+            if allocated.replace(size) == size {
+                return;
+            }
+            let gui = gui.clone();
+            glib::idle_add_local(move || {
+                gui.fit_grid();
+                glib::Continue(false)
+            });
+        });
+
+        let overlay = Overlay::new();
+        overlay.add(&stack);
+        overlay.add_overlay(&self.toast.revealer);
+        overlay.add_overlay(&self.notices.bar);
+
+        overlay.upcast::<gtk::Widget>()
+    }
+
+    fn chart_controls(&self) -> gtk::Box {
+        let controls = Box::new(Orientation::Horizontal, 5);
+
+        let chart_type = ComboBoxText::new();
+        for t in &[charts::ChartType::Bar, charts::ChartType::Line, charts::ChartType::StackedBar,
+                   charts::ChartType::Heatmap, charts::ChartType::Pie] {
+            chart_type.append(Some(t.id()), t.label());
+        }
+        chart_type.set_active_id(Some(charts::ChartType::default().id()));
+        let chart = self.chart.clone();
+        let chart_species = self.chart_species.clone();
+        chart_type.connect_changed(move |combo| {
+            if let Some(id) = combo.get_active_id() {
+                let chart_type = charts::ChartType::from_id(&id);
+                // Only the calendar is for a single species.
+                chart_species.set_visible(chart_type == charts::ChartType::Heatmap);
+                chart.set_settings(charts::ChartSettings { chart_type, ..chart.settings() });
+            }
+        });
+        controls.pack_start(&chart_type, false, false, 0);
+
+        self.populate_chart_species();
+        let chart = self.chart.clone();
+        let populating = self.populating.clone();
+        self.chart_species.connect_changed(move |combo| {
+            if populating.get() {
+                return;
+            }
+            if let Some(id) = combo.get_active_id() {
+                chart.set_settings(charts::ChartSettings { species: id.parse::<i64>().ok(), ..chart.settings() });
+            }
+        });
+        self.chart_species.set_no_show_all(true);
+        controls.pack_start(&self.chart_species, false, false, 0);
+
+        let export = Button::with_label("Export…");
+        let chart = self.chart.clone();
+        export.connect_clicked(move |_| export_chart(&chart));
+        controls.pack_start(&export, false, false, 0);
+
+        let metric = ComboBoxText::new();
+        for m in &[charts::Metric::Days, charts::Metric::TotalIndividuals, charts::Metric::MaxIndividuals] {
+            metric.append(Some(m.id()), m.label());
+        }
+        metric.set_active_id(Some(charts::Metric::default().id()));
+        let chart = self.chart.clone();
+        metric.connect_changed(move |combo| {
+            if let Some(id) = combo.get_active_id() {
+                chart.set_settings(charts::ChartSettings { metric: charts::Metric::from_id(&id), ..chart.settings() });
+            }
+        });
+        controls.pack_end(&metric, false, false, 0);
+
+        let granularity = ComboBoxText::new();
+        for g in &[charts::Granularity::Day, charts::Granularity::Week, charts::Granularity::Month, charts::Granularity::Year] {
+            granularity.append(Some(g.id()), g.label());
+        }
+        granularity.set_active_id(Some(charts::Granularity::default().id()));
+        let chart = self.chart.clone();
+        granularity.connect_changed(move |combo| {
+            if let Some(id) = combo.get_active_id() {
+                chart.set_settings(charts::ChartSettings { granularity: charts::Granularity::from_id(&id), ..chart.settings() });
+            }
+        });
+        controls.pack_end(&granularity, false, false, 0);
+
+        let range = ComboBoxText::new();
+        for r in &[charts::Range::Last30Days, charts::Range::Last12Months, charts::Range::ThisYear, charts::Range::AllTime] {
+            range.append(Some(r.id()), r.label());
+        }
+        range.set_active_id(Some(charts::Range::default().id()));
+        let chart = self.chart.clone();
+        range.connect_changed(move |combo| {
+            if let Some(id) = combo.get_active_id() {
+                chart.set_settings(charts::ChartSettings { range: charts::Range::from_id(&id), ..chart.settings() });
+            }
+        });
+        controls.pack_end(&range, false, false, 0);
+
+        self.populate_filter(&sites::SITES);
+        let chart = self.chart.clone();
+        let populating = self.populating.clone();
+        self.site_filter.connect_changed(move |combo| {
+            if populating.get() {
+                return;
+            }
+            if let Some(id) = combo.get_active_id() {
+                chart.set_settings(charts::ChartSettings { site_filter: sites::site_filter_from_id(&id), ..chart.settings() });
+            }
+        });
+        controls.pack_end(&self.site_filter, false, false, 0);
+
+        self.populate_filter(&observers::OBSERVERS);
+        let chart = self.chart.clone();
+        let populating = self.populating.clone();
+        self.observer_filter.connect_changed(move |combo| {
+            if populating.get() {
+                return;
+            }
+            if let Some(id) = combo.get_active_id() {
+                chart.set_settings(charts::ChartSettings { observer_id: observers::observer_filter_from_id(&id), ..chart.settings() });
+            }
+        });
+        controls.pack_end(&self.observer_filter, false, false, 0);
 
-        stack.upcast::<gtk::Widget>()
+        controls
     }
 
     pub fn refresh(&self) -> () {
         self.refresh_images();
-        charts::update_chart(&self.drawing_area);
+        self.populate_filter(&sites::SITES);
+        self.populate_filter(&observers::OBSERVERS);
+        self.populate_chart_species();
+        self.chart.refresh();
     }
 }
 
-pub fn image_dir() -> PathBuf {
-    let image_dir = ProjectDirs::from("org", "glenntrigg", "bird_counter")
+pub fn data_dir() -> PathBuf {
+    ProjectDirs::from("org", "glenntrigg", "bird_counter")
         .unwrap()
         .data_dir()
         .to_owned()
-        .join(Path::new("images"));
+}
+
+pub fn image_dir() -> PathBuf {
+    let image_dir = data_dir().join(Path::new("images"));
 
     image_dir
 }
 
+/// Shows an error in a dialog, waiting for it to be dismissed. Use `notices::notify` instead
+/// for anything that can happen while drawing or without someone there to see it.
 pub fn alert(message: &str) -> () {
+    notices::log(notices::Severity::Error, message);
     let alert = MessageDialog::new::<Window>(
         None,
         gtk::DialogFlags::DESTROY_WITH_PARENT,
@@ -118,3 +319,68 @@ pub fn alert(message: &str) -> () {
     alert.run();
     alert.hide();
 }
+
+/// Asks where to save the chart, and how big, then saves it there.
+fn export_chart(chart: &charts::ChartView) {
+    let dialog = FileChooserDialog::with_buttons::<Window>(
+        Some("Export chart"),
+        None,
+        gtk::FileChooserAction::Save,
+        &[("_Cancel", ResponseType::Cancel), ("_Save", ResponseType::Accept)],
+    );
+    dialog.set_do_overwrite_confirmation(true);
+    dialog.set_current_name("chart.png");
+    let filter = FileFilter::new();
+    filter.set_name(Some("PNG, SVG or PDF"));
+    for pattern in &["*.png", "*.svg", "*.pdf"] {
+        filter.add_pattern(pattern);
+    }
+    dialog.add_filter(&filter);
+
+    let size = Box::new(Orientation::Horizontal, 5);
+    let width = SpinButton::with_range(100.0, 10000.0, 10.0);
+    let height = SpinButton::with_range(100.0, 10000.0, 10.0);
+    width.set_value(1200.0);
+    height.set_value(800.0);
+    size.pack_start(&Label::new(Some("Size")), false, false, 0);
+    size.pack_start(&width, false, false, 0);
+    size.pack_start(&Label::new(Some("×")), false, false, 0);
+    size.pack_start(&height, false, false, 0);
+    size.show_all();
+    dialog.set_extra_widget(&size);
+
+    if dialog.run() == ResponseType::Accept {
+        if let Some(path) = dialog.get_filename() {
+            match chart.export(&path, width.get_value_as_int(), height.get_value_as_int()) {
+                Ok(()) => notices::notify(notices::Severity::Info, &format!("Saved the chart to {}", path.display())),
+                Err(error) => alert(&format!("Error exporting the chart - {}", error)),
+            }
+        }
+    }
+    dialog.hide();
+}
+
+/// Asks for a line of text, returning `None` if the user cancels or leaves it blank.
+pub fn prompt(title: &str, placeholder: &str) -> Option<String> {
+    let dialog = Dialog::with_buttons::<Window>(
+        Some(title),
+        None,
+        gtk::DialogFlags::MODAL,
+        &[("_Cancel", ResponseType::Cancel), ("_OK", ResponseType::Accept)],
+    );
+    dialog.set_default_response(ResponseType::Accept);
+    let entry = Entry::new();
+    entry.set_placeholder_text(Some(placeholder));
+    entry.set_activates_default(true);
+    dialog.get_content_area().add(&entry);
+    dialog.show_all();
+
+    let response = dialog.run();
+    let text = entry.get_text().trim().to_string();
+    dialog.hide();
+    if response == ResponseType::Accept && text != "" {
+        Some(text)
+    } else {
+        None
+    }
+}
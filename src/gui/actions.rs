@@ -0,0 +1,109 @@
+extern crate gtk;
+
+use crate::gui;
+use crate::gui::notices::{notify, Severity};
+use crate::reports;
+use crate::Animal;
+use gdk::prelude::*;
+use gio::prelude::*;
+use gtk::prelude::*;
+use gio::SimpleAction;
+use turbosql::select;
+
+impl gui::Gui {
+
+    /// Registers the application's actions, so a running instance can be driven over D-Bus,
+    /// e.g. `gapplication action com.github.ggtrigg.bird_counter log-sighting "'Galah'"`.
+    /// Species are given by name, species code or id, and `log-sightings` takes a count too,
+    /// e.g. `"('Galah', 12)"`.
+    pub fn add_actions(&self, app: &gtk::Application) {
+        let string = glib::VariantTy::new("s").ok();
+
+        let action = SimpleAction::new("log-sighting", string);
+        let gui = self.clone();
+        action.connect_activate(move |_, param| {
+            if let Some(species) = param.and_then(|p| p.get::<String>()) {
+                log(&gui, &species, 1);
+            }
+        });
+        app.add_action(&action);
+
+        let action = SimpleAction::new("log-sightings", glib::VariantTy::new("(si)").ok());
+        let gui = self.clone();
+        action.connect_activate(move |_, param| {
+            if let Some((species, individuals)) = param.and_then(|p| p.get::<(String, i32)>()) {
+                log(&gui, &species, individuals as i64);
+            }
+        });
+        app.add_action(&action);
+
+        let action = SimpleAction::new("clear-sighting", string);
+        let gui = self.clone();
+        action.connect_activate(move |_, param| {
+            if let Some(species) = param.and_then(|p| p.get::<String>()) {
+                match lookup(&species).and_then(|animal| crate::clear_sighting(animal.rowid.unwrap_or(0))) {
+                    Ok(_) => gui.toast.show(&format!("{} cleared for today", species), None),
+                    Err(error) => notify(Severity::Error, &format!("Error clearing sightings of {} - {}", species, error)),
+                }
+                gui.refresh();
+            }
+        });
+        app.add_action(&action);
+
+        let action = SimpleAction::new("show-charts", None);
+        let stack = self.stack.clone();
+        action.connect_activate(move |_, _| {
+            stack.set_visible_child_full("charts", gtk::StackTransitionType::SlideLeft);
+        });
+        app.add_action(&action);
+
+        let action = SimpleAction::new("show-birds", None);
+        let stack = self.stack.clone();
+        action.connect_activate(move |_, _| {
+            stack.set_visible_child_full("birds", gtk::StackTransitionType::SlideRight);
+        });
+        app.add_action(&action);
+
+        let action = SimpleAction::new("toggle-fullscreen", None);
+        let window = self.window.clone();
+        action.connect_activate(move |_, _| {
+            let is_fullscreen = window.get_window()
+                .map(|w| w.get_state().contains(gdk::WindowState::FULLSCREEN))
+                .unwrap_or(false);
+            if is_fullscreen {
+                window.unfullscreen();
+            } else {
+                window.fullscreen();
+            }
+        });
+        app.add_action(&action);
+
+        let action = SimpleAction::new("refresh", None);
+        let gui = self.clone();
+        action.connect_activate(move |_, _| gui.refresh());
+        app.add_action(&action);
+    }
+}
+
+fn log(gui: &gui::Gui, species: &str, individuals: i64) {
+    let result = if individuals < 1 {
+        Err("individuals must be at least 1".into())
+    } else {
+        lookup(species).and_then(reports::loggable)
+            .and_then(|animal| crate::log_sighting(animal.rowid.unwrap_or(0), individuals))
+    };
+    match result {
+        Ok(_) if individuals == 1 => gui.toast.show(&format!("{} logged", species), None),
+        Ok(_) => gui.toast.show(&format!("{} × {} logged", individuals, species), None),
+        Err(error) => notify(Severity::Error, &format!("Error logging sighting of {} - {}", species, error)),
+    }
+    gui.refresh();
+}
+
+fn lookup(species: &str) -> Result<Animal, Box<dyn std::error::Error>> {
+    match species.parse::<i64>() {
+        Ok(animal_id) => select!(Animal "where rowid = ?", animal_id)
+            .map_err(|_| format!("no species with id {}", animal_id).into()),
+        Err(_) => reports::find_animal(species),
+    }
+}
@@ -0,0 +1,60 @@
+//! Per-species values that are expensive to work out on every redraw: scaled tile images
+//! and today's counts. Only the GUI thread touches these.
+
+use gdk_pixbuf::Pixbuf;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// Beyond this many images (e.g. after a lot of resizing) the cache is emptied and refilled.
+const MAX_PIXBUFS: usize = 256;
+
+/// (species, width, height in device pixels, device pixels per logical one, whether it's ticked)
+type PixbufKey = (i64, i32, i32, i32, bool);
+
+thread_local! {
+    static PIXBUFS: RefCell<HashMap<PixbufKey, Pixbuf>> = RefCell::new(HashMap::new());
+    static TODAY: RefCell<HashMap<i64, i64>> = RefCell::new(HashMap::new());
+}
+
+/// A species' image at a size, loaded with `load` the first time it's needed.
+/// Nothing is kept when it can't be loaded, so it's tried again next time.
+pub fn pixbuf(animal_id: i64, width: i32, height: i32, scale: i32, ticked: bool, load: impl FnOnce() -> Option<Pixbuf>) -> Option<Pixbuf> {
+    let key = (animal_id, width, height, scale, ticked);
+    if let Some(pixbuf) = PIXBUFS.with(|pixbufs| pixbufs.borrow().get(&key).cloned()) {
+        return Some(pixbuf);
+    }
+    let pixbuf = load()?;
+    PIXBUFS.with(|pixbufs| {
+        let mut pixbufs = pixbufs.borrow_mut();
+        if pixbufs.len() >= MAX_PIXBUFS {
+            pixbufs.clear();
+        }
+        pixbufs.insert(key, pixbuf.clone());
+    });
+    Some(pixbuf)
+}
+
+/// How many of a species have been counted today, worked out with `count` when not known.
+pub fn today(animal_id: i64, count: impl FnOnce() -> i64) -> i64 {
+    if let Some(today) = TODAY.with(|today| today.borrow().get(&animal_id).copied()) {
+        return today;
+    }
+    let today = count();
+    TODAY.with(|counts| counts.borrow_mut().insert(animal_id, today));
+    today
+}
+
+/// Forgets today's counts, after sightings change or the active site or observer does.
+pub fn forget_sightings() {
+    TODAY.with(|today| today.borrow_mut().clear());
+}
+
+/// Forgets a species' count after it's logged or cleared.
+pub fn forget_today(animal_id: i64) {
+    TODAY.with(|today| today.borrow_mut().remove(&animal_id));
+}
+
+/// Forgets a species' images after its image is replaced or it's deleted.
+pub fn forget_image(animal_id: i64) {
+    PIXBUFS.with(|pixbufs| pixbufs.borrow_mut().retain(|key, _| key.0 != animal_id));
+}
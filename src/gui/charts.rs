@@ -1,123 +1,1247 @@
-use std::iter::{FromIterator, Iterator};
-// use crate::gui;
+mod plots;
+
+use super::notices::{notify, Severity};
+use crate::colors::{self, Rgb};
 use primitives::colorspace::prelude::*;
-use charts::{Chart, BarChart, BarChartOptions, Position, Fill};
+use charts::{
+    Chart, LineChart, LineChartOptions, PieChart, PieChartOptions, Position, Fill,
+};
 use animate::Canvas;
 use dataflow::*;
 use intmap::IntMap;
 use chrono::prelude::*;
+use chrono::Duration;
 use gtk::prelude::*;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fs::File;
+use std::iter::successors;
+use std::path::Path;
+use std::rc::Rc;
 use turbosql::select;
 
 #[derive(Debug, Eq, PartialEq, Clone)]
 struct ChannelData {
     name: Option<String>,
-    tag: Option<u8>
+    animal_id: Option<i64>,
+    color: Option<String>,
+    archived_at: Option<i64>,
 }
 
+/// Individuals logged of one species at one site on one day.
 #[derive(Debug, Eq, PartialEq, Clone)]
-struct WeekAndCountResult {
-    week: Option<String>,
-    count: Option<u32>,
-}
-
-
-fn get_sightings(animal_id: u32) -> Vec<WeekAndCountResult> {
-    let result = select!(Vec<WeekAndCountResult> r#"strftime("%Y%W", seen_at, "unixepoch", "localtime") as week, count(distinct date(seen_at, "unixepoch", "localtime")) as count from sighting where animal_id = ? group by week"#, animal_id);
-    match result {
-        Ok(rows) => rows,
-        Err(_) => Vec::new()
-    }
-}
-
-fn create_stream() -> DataStream<String, i32> {
-    let mut metadata = Vec::new();
-    let an = turbosql::select!(Vec<ChannelData> "name, rowid as tag from animal").expect("Couldn't retrieve animals");
-    
-    // Get the start and end (current) week values, and derive the x-axis week labels
-    let first_week: String = select!(String r#"min(strftime("%Y%W", seen_at, "unixepoch", "localtime")) as week from sighting"#).expect("Error accesing db");
-    let this_week = Local::now().format("%Y%W").to_string();
-    let first_week_n = first_week.parse::<u32>().unwrap();
-    let this_week_n = this_week.parse::<u32>().unwrap();
-    let weeks = Vec::from_iter(first_week_n..=this_week_n);
-    let week_labels: Vec<String> = weeks.iter().enumerate().map(|(i, _)| {
-        match i {
-            0 => String::from("This week"),
-            1 => String::from("Last week"),
-            _ => (i as u32 + 1).to_string()
-        }
-    }).rev().collect();
-    
-    for cdata in an {
-        metadata.push(Channel {
-           name: cdata.name.clone().unwrap(),
-           tag: cdata.tag.unwrap(),
-           visible: true 
+struct DayTotal {
+    animal_id: Option<i64>,
+    site_id: Option<i64>,
+    day: Option<String>,
+    individuals: Option<i64>,
+}
+
+/// Daily totals for every species, kept between refreshes so that changing what the chart
+/// shows, or logging a sighting, doesn't mean reading every sighting again.
+#[derive(Debug, Default)]
+struct ChartData {
+    /// The observer the totals are for, or `None` for everyone.
+    observer_id: Option<i64>,
+    species: Vec<ChannelData>,
+    /// Species id → (day as YYYY-MM-DD, site id or 0 for none) → individuals.
+    totals: HashMap<i64, BTreeMap<(String, i64), i64>>,
+}
+
+impl ChartData {
+    /// Reads every species' daily totals with one query.
+    fn load(observer_id: Option<i64>) -> Result<ChartData, Box<dyn Error>> {
+        let species = select!(Vec<ChannelData> "name, rowid as animal_id, color, archived_at from animal order by name")?;
+        // Sightings from before individuals were recorded count as a single bird.
+        let rows = select!(Vec<DayTotal> r#"animal_id, ifnull(site_id, 0) as site_id, date(seen_at, "unixepoch", "localtime") as day, sum(coalesce(individuals, 1)) as individuals from sighting where deleted_at is null and (? is null or observer_id = ?) group by animal_id, ifnull(site_id, 0), day"#,
+            observer_id, observer_id)?;
+        let mut data = ChartData { observer_id, species, totals: HashMap::new() };
+        data.add(rows);
+        Ok(data)
+    }
+
+    /// Reads the species and today's totals again, keeping the totals of earlier days as they were.
+    fn reload_today(&mut self) -> Result<(), Box<dyn Error>> {
+        let species = select!(Vec<ChannelData> "name, rowid as animal_id, color, archived_at from animal order by name")?;
+        let today = Local::now().format("%Y-%m-%d").to_string();
+        let rows = select!(Vec<DayTotal> r#"animal_id, ifnull(site_id, 0) as site_id, date(seen_at, "unixepoch", "localtime") as day, sum(coalesce(individuals, 1)) as individuals from sighting where deleted_at is null and (? is null or observer_id = ?) and date(seen_at, "unixepoch", "localtime") = ? group by animal_id, ifnull(site_id, 0)"#,
+            self.observer_id, self.observer_id, today)?;
+        self.species = species;
+        for days in self.totals.values_mut() {
+            days.retain(|(d, _), _| *d != today);
+        }
+        self.totals.retain(|_, days| !days.is_empty());
+        self.add(rows);
+        Ok(())
+    }
+
+    /// Reads one species' totals for one day again, after a sighting of it was logged or cleared.
+    fn reload_day(&mut self, animal_id: i64, day: &str) -> Result<(), Box<dyn Error>> {
+        let rows = select!(Vec<DayTotal> r#"animal_id, ifnull(site_id, 0) as site_id, date(seen_at, "unixepoch", "localtime") as day, sum(coalesce(individuals, 1)) as individuals from sighting where deleted_at is null and (? is null or observer_id = ?) and animal_id = ? and date(seen_at, "unixepoch", "localtime") = ? group by ifnull(site_id, 0)"#,
+            self.observer_id, self.observer_id, animal_id, day)?;
+        if let Some(days) = self.totals.get_mut(&animal_id) {
+            days.retain(|(d, _), _| d != day);
+        }
+        self.add(rows);
+        Ok(())
+    }
+
+    fn add(&mut self, rows: Vec<DayTotal>) {
+        for row in rows {
+            if let (Some(animal_id), Some(day)) = (row.animal_id, row.day) {
+                self.totals.entry(animal_id).or_default()
+                    .insert((day, row.site_id.unwrap_or(0)), row.individuals.unwrap_or(0));
+            }
+        }
+    }
+
+    /// The species with sightings to chart.
+    fn seen_species(&self) -> impl Iterator<Item = &ChannelData> {
+        self.species.iter().filter(move |species| {
+            species.animal_id.map(|id| self.totals.contains_key(&id)).unwrap_or(false)
+        })
+    }
+
+    /// A species' totals per day, counting only sightings at `site_id` if one is given.
+    fn days(&self, animal_id: i64, site_id: Option<i64>) -> BTreeMap<&str, i64> {
+        let mut days = BTreeMap::new();
+        if let Some(totals) = self.totals.get(&animal_id) {
+            for ((day, site), individuals) in totals {
+                if site_id.map(|id| id == *site).unwrap_or(true) {
+                    *days.entry(day.as_str()).or_insert(0) += individuals;
+                }
+            }
+        }
+        days
+    }
+}
+
+fn parse_day(day: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(day, "%Y-%m-%d").ok()
+}
+
+/// The Monday starting the calendar (ISO 8601) week a day falls in.
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+/// What the chart plots for each species in each period.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Metric {
+    /// Number of distinct days the species was seen.
+    Days,
+    /// Sum of all individuals logged.
+    TotalIndividuals,
+    /// Largest number of individuals logged on a single day.
+    MaxIndividuals,
+}
+
+impl Metric {
+    pub fn id(&self) -> &'static str {
+        match self {
+            Metric::Days => "days",
+            Metric::TotalIndividuals => "total",
+            Metric::MaxIndividuals => "max",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Metric {
+        match id {
+            "total" => Metric::TotalIndividuals,
+            "max" => Metric::MaxIndividuals,
+            _ => Metric::Days,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Metric::Days => "Days seen",
+            Metric::TotalIndividuals => "Total individuals",
+            Metric::MaxIndividuals => "Max individuals",
+        }
+    }
+
+    fn title(&self, granularity: Granularity) -> String {
+        match self {
+            Metric::Days => format!("{} Bird Sightings", granularity.adjective()),
+            Metric::TotalIndividuals => format!("{} Bird Count (total)", granularity.adjective()),
+            Metric::MaxIndividuals => format!("{} Bird Count (daily max)", granularity.adjective()),
+        }
+    }
+}
+
+impl Default for Metric {
+    fn default() -> Metric {
+        Metric::Days
+    }
+}
+
+/// How long a period each bar (or point) of the chart covers.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Granularity {
+    Day,
+    /// Calendar weeks, Monday to Sunday.
+    Week,
+    Month,
+    Year,
+}
+
+impl Granularity {
+    pub fn id(&self) -> &'static str {
+        match self {
+            Granularity::Day => "day",
+            Granularity::Week => "week",
+            Granularity::Month => "month",
+            Granularity::Year => "year",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Granularity {
+        match id {
+            "day" => Granularity::Day,
+            "month" => Granularity::Month,
+            "year" => Granularity::Year,
+            _ => Granularity::Week,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Granularity::Day => "By day",
+            Granularity::Week => "By week",
+            Granularity::Month => "By month",
+            Granularity::Year => "By year",
+        }
+    }
+
+    fn adjective(&self) -> &'static str {
+        match self {
+            Granularity::Day => "Daily",
+            Granularity::Week => "Weekly",
+            Granularity::Month => "Monthly",
+            Granularity::Year => "Yearly",
+        }
+    }
+
+    fn axis_title(&self) -> &'static str {
+        match self {
+            Granularity::Day => "Day",
+            Granularity::Week => "Week starting",
+            Granularity::Month => "Month",
+            Granularity::Year => "Year",
+        }
+    }
+
+    /// The first day of the period a day falls in.
+    fn start(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Granularity::Day => date,
+            Granularity::Week => week_start(date),
+            Granularity::Month => NaiveDate::from_ymd(date.year(), date.month(), 1),
+            Granularity::Year => NaiveDate::from_ymd(date.year(), 1, 1),
+        }
+    }
+
+    /// The first day of the period after the one starting on `start`.
+    fn next(&self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Granularity::Day => start + Duration::days(1),
+            Granularity::Week => start + Duration::weeks(1),
+            Granularity::Month => match start.month() {
+                12 => NaiveDate::from_ymd(start.year() + 1, 1, 1),
+                month => NaiveDate::from_ymd(start.year(), month + 1, 1),
+            },
+            Granularity::Year => NaiveDate::from_ymd(start.year() + 1, 1, 1),
+        }
+    }
+
+    /// Labels a period by its start, adding the year when the chart spans more than one.
+    fn label_for(&self, start: NaiveDate, several_years: bool) -> String {
+        let format = match (self, several_years) {
+            (Granularity::Day, false) | (Granularity::Week, false) => "%-d %b",
+            (Granularity::Day, true) | (Granularity::Week, true) => "%-d %b %Y",
+            (Granularity::Month, false) => "%b",
+            (Granularity::Month, true) => "%b %Y",
+            (Granularity::Year, _) => "%Y",
+        };
+        start.format(format).to_string()
+    }
+}
+
+impl Default for Granularity {
+    fn default() -> Granularity {
+        Granularity::Week
+    }
+}
+
+/// How far back the chart goes.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Range {
+    Last30Days,
+    Last12Months,
+    ThisYear,
+    AllTime,
+}
+
+impl Range {
+    pub fn id(&self) -> &'static str {
+        match self {
+            Range::Last30Days => "30days",
+            Range::Last12Months => "12months",
+            Range::ThisYear => "year",
+            Range::AllTime => "all",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Range {
+        match id {
+            "30days" => Range::Last30Days,
+            "12months" => Range::Last12Months,
+            "year" => Range::ThisYear,
+            _ => Range::AllTime,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Range::Last30Days => "Last 30 days",
+            Range::Last12Months => "Last 12 months",
+            Range::ThisYear => "This year",
+            Range::AllTime => "All time",
+        }
+    }
+
+    /// The first day included, or `None` to start from the first sighting.
+    fn first_day(&self, today: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Range::Last30Days => Some(today - Duration::days(29)),
+            Range::Last12Months => Some(today - Duration::days(364)),
+            Range::ThisYear => Some(NaiveDate::from_ymd(today.year(), 1, 1)),
+            Range::AllTime => None,
+        }
+    }
+}
+
+impl Default for Range {
+    fn default() -> Range {
+        Range::AllTime
+    }
+}
+
+/// Which sites' sightings the chart includes.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum SiteFilter {
+    All,
+    Only(i64),
+    /// One channel per species and site, with 0 standing for sightings logged without a site.
+    Split,
+}
+
+impl Default for SiteFilter {
+    fn default() -> SiteFilter {
+        SiteFilter::All
+    }
+}
+
+/// A single channel's worth of chart data: a species, optionally restricted to one site.
+struct Series {
+    name: String,
+    animal_id: i64,
+    site_id: Option<i64>,
+    color: Rgb,
+}
+
+/// Chart data laid out as a value for each channel (a species, or a species at a site) in each
+/// period. The ux-charts charts are given it as a `DataStream`; the others draw it directly.
+#[derive(Debug, Default, Clone)]
+struct Table {
+    channels: Vec<String>,
+    /// The species and site (if it's only for one) of each channel.
+    animal_ids: Vec<i64>,
+    site_ids: Vec<Option<i64>>,
+    /// Each channel's species' colour.
+    colors: Vec<Rgb>,
+    /// Whether each channel is shown, or its species has been hidden with the legend.
+    visible: Vec<bool>,
+    periods: Vec<NaiveDate>,
+    /// The first and last day the table covers, which can be partway through a period.
+    first_day: Option<NaiveDate>,
+    last_day: Option<NaiveDate>,
+    labels: Vec<String>,
+    /// `values[channel][period]`
+    values: Vec<Vec<i32>>,
+    /// How many days each channel was seen on over the whole range.
+    days_seen: Vec<i32>,
+    /// `days[channel][period]`, whatever's being charted, for the tooltips.
+    days: Vec<Vec<i32>>,
+    /// `birds[channel][period]`, likewise.
+    birds: Vec<Vec<i32>>,
+}
+
+/// ux-charts tags each channel with a byte, so no more than this many can be charted by it.
+const MAX_CHANNELS: usize = u8::MAX as usize + 1;
+
+impl Table {
+    /// The table as ux-charts takes it, leaving off any channels past `MAX_CHANNELS`.
+    fn to_stream(&self) -> DataStream<String, i32> {
+        if self.channels.len() > MAX_CHANNELS {
+            notify(Severity::Warning, &format!("Only the first {} of {} channels can be charted as lines",
+                MAX_CHANNELS, self.channels.len()));
+        }
+        let metadata: Vec<Channel> = self.channels.iter().take(MAX_CHANNELS).enumerate()
+            .map(|(i, name)| Channel { name: name.clone(), tag: i as u8, visible: self.visible[i] })
+            .collect();
+        let mut frames = Vec::new();
+        for (i, label) in self.labels.iter().enumerate() {
+            let mut imap: IntMap<i32> = IntMap::with_capacity(metadata.len());
+            for (bnum, row) in self.values.iter().take(MAX_CHANNELS).enumerate() {
+                imap.insert(bnum as u64, row[i]);
+            }
+            frames.push(DataFrame {
+               metric: label.clone(),
+               data: imap
+            });
+        }
+        DataStream::new(metadata, frames)
+    }
+
+    /// Whether a channel gets a slice of the pie.
+    fn in_pie(&self, channel: usize) -> bool {
+        self.visible[channel] && self.days_seen[channel] > 0
+    }
+
+    /// The share of days each shown channel was seen on, as a slice each.
+    fn pie_stream(&self) -> DataStream<String, i32> {
+        let metadata = vec![Channel { name: String::from("Days seen"), tag: 0, visible: true }];
+        let frames = self.channels.iter().zip(&self.days_seen).enumerate()
+            .filter(|(i, _)| self.in_pie(*i))
+            .map(|(_, (name, days))| {
+                let mut imap: IntMap<i32> = IntMap::with_capacity(1);
+                imap.insert(0, *days);
+                DataFrame { metric: name.clone(), data: imap }
+            })
+            .collect();
+        DataStream::new(metadata, frames)
+    }
+
+    /// The colours of the slices in `pie_stream`.
+    fn pie_colors(&self) -> Vec<Rgb> {
+        self.colors.iter().enumerate()
+            .filter(|(i, _)| self.in_pie(*i))
+            .map(|(_, color)| *color)
+            .collect()
+    }
+}
+
+/// The first and last day of one of a table's periods, leaving off any days the table doesn't cover.
+fn period_days(table: &Table, period: usize, granularity: Granularity) -> (NaiveDate, NaiveDate) {
+    let start = table.periods[period];
+    let end = granularity.next(start) - Duration::days(1);
+    (
+        table.first_day.map_or(start, |day| day.max(start)),
+        table.last_day.map_or(end, |day| day.min(end)),
+    )
+}
+
+/// A range of days for people to read, e.g. "4 Jan – 10 Jan 2021".
+fn date_range(first_day: NaiveDate, last_day: NaiveDate) -> String {
+    if first_day == last_day {
+        first_day.format("%-d %b %Y").to_string()
+    } else if first_day.year() == last_day.year() {
+        format!("{} – {}", first_day.format("%-d %b"), last_day.format("%-d %b %Y"))
+    } else {
+        format!("{} – {}", first_day.format("%-d %b %Y"), last_day.format("%-d %b %Y"))
+    }
+}
+
+/// The first day the chart covers: the start of its range, or the first sighting for all time.
+fn first_day(data: &ChartData, range: Range, today: NaiveDate) -> NaiveDate {
+    range.first_day(today).unwrap_or_else(|| {
+        data.totals.values()
+            .filter_map(|days| days.keys().next())
+            .filter_map(|(day, _)| parse_day(day))
+            .min()
+            .unwrap_or(today)
+    })
+}
+
+fn create_table(data: &ChartData, settings: &ChartSettings, hidden: &BTreeSet<i64>) -> Table {
+    let ChartSettings { metric, site_filter, granularity, range, .. } = *settings;
+    let mut table = Table::default();
+
+    // Every period in the range up to the current one, labelled with the date each starts on.
+    let today = Local::today().naive_local();
+    let (first_day, last_day) = match settings.drill {
+        Some(drill) => (drill.first_day, drill.last_day),
+        None => (first_day(data, range, today), today),
+    };
+    let first_period = granularity.start(first_day);
+    let periods: Vec<NaiveDate> = successors(Some(first_period), |period| Some(granularity.next(*period)))
+        .take_while(|period| *period <= last_day)
+        .collect();
+    let several_years = first_period.year() != today.year();
+    table.labels = periods.iter().map(|period| granularity.label_for(*period, several_years)).collect();
+    let period_index: HashMap<NaiveDate, usize> = periods.iter().enumerate().map(|(i, period)| (*period, i)).collect();
+
+    let mut sites: Vec<(i64, String)> = Vec::new();
+    if site_filter == SiteFilter::Split {
+        sites = super::sites::sites().into_iter()
+            .filter_map(|site| Some((site.rowid?, site.name?)))
+            .collect();
+        sites.push((0, String::from("no site")));
+    }
+    let mut series = Vec::new();
+    for cdata in &data.species {
+        let (name, animal_id) = match (cdata.name.clone(), cdata.animal_id) {
+            (Some(name), Some(animal_id)) => (name, animal_id),
+            _ => continue,
+        };
+        let color = cdata.color.as_deref().and_then(colors::parse).unwrap_or(colors::UNASSIGNED);
+        if let Some(drill) = settings.drill {
+            if animal_id == drill.animal_id {
+                series.push(Series { name, animal_id, site_id: drill.site_id, color });
+            }
+            continue;
+        }
+        match site_filter {
+            SiteFilter::All => series.push(Series { name, animal_id, site_id: None, color }),
+            SiteFilter::Only(site_id) => series.push(Series { name, animal_id, site_id: Some(site_id), color }),
+            SiteFilter::Split => {
+                // Each site the species was seen at gets its own shade of the species' colour.
+                let seen: Vec<&(i64, String)> = sites.iter()
+                    .filter(|(site_id, _)| !data.days(animal_id, Some(*site_id)).is_empty())
+                    .collect();
+                for (i, (site_id, site_name)) in seen.iter().enumerate() {
+                    series.push(Series {
+                        name: format!("{} @ {}", name, site_name),
+                        animal_id,
+                        site_id: Some(*site_id),
+                        color: colors::lighten(color, 0.7 * i as f64 / seen.len() as f64),
+                    });
+                }
+            }
+        }
+    }
+
+    for s in series {
+        let mut row = vec![0; periods.len() as usize];
+        let mut days_row = vec![0; periods.len() as usize];
+        let mut birds_row = vec![0; periods.len() as usize];
+        let mut days_seen = 0;
+        let days = data.days(s.animal_id, s.site_id);
+        for (day, individuals) in days {
+            let day = match parse_day(day) {
+                Some(day) if day >= first_day && day <= last_day => day,
+                _ => continue,
+            };
+            days_seen += 1;
+            if let Some(&j) = period_index.get(&granularity.start(day)) {
+                let individuals = individuals as i32;
+                days_row[j] += 1;
+                birds_row[j] += individuals;
+                match metric {
+                    Metric::Days => row[j] += 1,
+                    Metric::TotalIndividuals => row[j] += individuals,
+                    Metric::MaxIndividuals => row[j] = row[j].max(individuals),
+                }
+            }
+        }
+        table.channels.push(s.name);
+        table.animal_ids.push(s.animal_id);
+        table.site_ids.push(s.site_id);
+        table.colors.push(s.color);
+        table.visible.push(!hidden.contains(&s.animal_id));
+        table.values.push(row);
+        table.days.push(days_row);
+        table.birds.push(birds_row);
+        table.days_seen.push(days_seen);
+    }
+    table.periods = periods;
+    table.first_day = Some(first_day);
+    table.last_day = Some(last_day);
+
+    table
+}
+
+/// The table a chart is drawn from. The calendar heatmap takes one of its channels a day at a time.
+fn chart_table(data: &ChartData, settings: &ChartSettings, hidden: &BTreeSet<i64>) -> Table {
+    match settings.chart_type {
+        ChartType::Heatmap => create_table(data, &ChartSettings { granularity: Granularity::Day, drill: None, ..*settings }, hidden),
+        _ => create_table(data, settings, hidden),
+    }
+}
+
+/// The calendar heatmap of one channel of a day-by-day `table`: the first shown one of the
+/// species picked, or else of the first species seen that's still being counted.
+fn create_heatmap(data: &ChartData, settings: &ChartSettings, table: &Table) -> plots::Heatmap {
+    let counted = |animal_id: i64| data.species.iter()
+        .any(|s| s.animal_id == Some(animal_id) && s.archived_at.is_none());
+    let animal_id = settings.species.or_else(|| {
+        (0..table.channels.len())
+            .find(|i| table.visible[*i] && table.days_seen[*i] > 0 && counted(table.animal_ids[*i]))
+            .map(|i| table.animal_ids[i])
+    });
+    // A species that's been picked is shown even if it's hidden in the legend.
+    let channels: Vec<usize> = (0..table.channels.len())
+        .filter(|i| Some(table.animal_ids[*i]) == animal_id && (settings.species.is_some() || table.visible[*i]))
+        .collect();
+    // Split by site, the species has a channel per site it was seen at.
+    let channel = channels.iter().copied().find(|i| table.days_seen[*i] > 0).or_else(|| channels.first().copied());
+    let mut heatmap = plots::Heatmap::default();
+    if let (Some(first_day), Some(last_day)) = (table.first_day, table.last_day) {
+        heatmap.first_day = first_day;
+        heatmap.last_day = last_day;
+    }
+    if let Some(i) = channel {
+        heatmap.title = format!("{} — {}", table.channels[i], settings.metric.label());
+        heatmap.color = table.colors[i];
+        heatmap.values = table.periods.iter().zip(&table.values[i])
+            .filter(|(_, value)| **value > 0)
+            .map(|(day, value)| (*day, *value as i64))
+            .collect();
+    }
+    heatmap
+}
+
+/// The kinds of chart that can be drawn.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum ChartType {
+    Bar,
+    /// Each species' trend over time.
+    Line,
+    /// The mix of species in each period.
+    StackedBar,
+    /// One species, a square per day.
+    Heatmap,
+    /// Each species' share of the days birds were seen.
+    Pie,
+}
+
+impl ChartType {
+    pub fn id(&self) -> &'static str {
+        match self {
+            ChartType::Bar => "bar",
+            ChartType::Line => "line",
+            ChartType::StackedBar => "stacked",
+            ChartType::Heatmap => "heatmap",
+            ChartType::Pie => "pie",
+        }
+    }
+
+    pub fn from_id(id: &str) -> ChartType {
+        match id {
+            "line" => ChartType::Line,
+            "stacked" => ChartType::StackedBar,
+            "heatmap" => ChartType::Heatmap,
+            "pie" => ChartType::Pie,
+            _ => ChartType::Bar,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ChartType::Bar => "Bars",
+            ChartType::Line => "Lines",
+            ChartType::StackedBar => "Stacked bars",
+            ChartType::Heatmap => "Calendar",
+            ChartType::Pie => "Pie",
+        }
+    }
+}
+
+impl Default for ChartType {
+    fn default() -> ChartType {
+        ChartType::Bar
+    }
+}
+
+/// What the chart shows, as picked with the controls above it.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ChartSettings {
+    pub chart_type: ChartType,
+    pub metric: Metric,
+    pub site_filter: SiteFilter,
+    pub observer_id: Option<i64>,
+    pub granularity: Granularity,
+    pub range: Range,
+    /// The species the calendar heatmap is for, or `None` for the first.
+    pub species: Option<i64>,
+    /// Set by the chart while it shows the days of one bar.
+    pub drill: Option<Drill>,
+}
+
+/// One species' bar for one period, shown a day at a time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Drill {
+    pub animal_id: i64,
+    pub site_id: Option<i64>,
+    pub first_day: NaiveDate,
+    pub last_day: NaiveDate,
+}
+
+/// A chart of any type, ready to draw. The ux-charts charts take their colours when they're
+/// made, so those are kept alongside to tell when a chart has to be made again.
+enum Plot {
+    Line(LineChart<Canvas, String, i32>, Vec<Rgb>),
+    Pie(PieChart<Canvas, String, i32>, Vec<Rgb>),
+    /// Side by side or stacked, drawn here so that which bar is tapped on can be told exactly.
+    Bars(plots::Bars),
+    Heatmap(plots::Heatmap),
+}
+
+impl Plot {
+    fn new(settings: &ChartSettings, colors: &[Rgb]) -> Plot {
+        match settings.chart_type {
+            ChartType::Bar | ChartType::StackedBar => Plot::Bars(plots::Bars {
+                title: settings.metric.title(settings.granularity),
+                axis_title: settings.granularity.axis_title().to_string(),
+                stacked: settings.chart_type == ChartType::StackedBar,
+                table: Table::default(),
+            }),
+            ChartType::Line => Plot::Line(LineChart::new(line_options(settings, colors)), colors.to_vec()),
+            ChartType::Pie => Plot::Pie(PieChart::new(pie_options(colors)), colors.to_vec()),
+            ChartType::Heatmap => Plot::Heatmap(plots::Heatmap::default()),
+        }
+    }
+
+    fn colors(&self) -> &[Rgb] {
+        match self {
+            Plot::Line(_, colors) | Plot::Pie(_, colors) => &colors[..],
+            Plot::Bars(chart) => &chart.table.colors[..],
+            Plot::Heatmap(_) => &[],
+        }
+    }
+
+    /// Gives the chart new data from `table`, which the heatmap takes one channel of.
+    fn set_data(&mut self, data: &ChartData, settings: &ChartSettings, table: &Table) {
+        if let Plot::Heatmap(chart) = self {
+            *chart = create_heatmap(data, settings, table);
+            return;
+        }
+        let colors = match self {
+            Plot::Pie(..) => table.pie_colors(),
+            _ => table.colors.clone(),
+        };
+        // A species was added, hidden or recoloured.
+        if self.colors() != &colors[..] {
+            *self = Plot::new(settings, &colors);
+        }
+        match self {
+            Plot::Line(chart, _) => chart.set_stream(table.to_stream()),
+            Plot::Pie(chart, _) => chart.set_stream(table.pie_stream()),
+            Plot::Bars(chart) => chart.table = table.clone(),
+            Plot::Heatmap(_) => (),
+        }
+    }
+
+    fn draw(&self, cr: &cairo::Context, width: f64, height: f64) {
+        match self {
+            Plot::Line(chart, _) => {
+                chart.resize(width, height);
+                chart.draw(&Canvas::new(cr));
+            }
+            Plot::Pie(chart, _) => {
+                chart.resize(width, height);
+                chart.draw(&Canvas::new(cr));
+            }
+            Plot::Bars(chart) => chart.draw(cr, width, height),
+            Plot::Heatmap(chart) => chart.draw(cr, width, height),
+        }
+    }
+
+    /// The channel and period of the bar at a point, if this is a bar chart and there's one there.
+    fn bar_at(&self, x: f64, y: f64, width: f64, height: f64) -> Option<(usize, usize)> {
+        match self {
+            Plot::Bars(chart) => chart.bar_at(x, y, width, height),
+            _ => None,
+        }
+    }
+}
+
+/// The species hidden with the chart's legend.
+fn hidden_species() -> BTreeSet<i64> {
+    crate::get_setting("hidden_species").unwrap_or_default()
+        .split(',')
+        .filter_map(|id| id.parse::<i64>().ok())
+        .collect()
+}
+
+/// Draws a chart into a PNG, SVG or PDF file, going by its extension, `width` by `height`
+/// pixels (or points, for SVG and PDF). The species hidden on the chart page are left out.
+/// As the legend on the chart page isn't part of the chart, one is drawn underneath.
+pub fn export_chart(settings: &ChartSettings, path: &Path, width: i32, height: i32) -> Result<(), Box<dyn Error>> {
+    let data = ChartData::load(settings.observer_id)?;
+    let table = chart_table(&data, settings, &hidden_species());
+    let mut plot = Plot::new(settings, &[]);
+    plot.set_data(&data, settings, &table);
+    let legend: Vec<(String, Rgb)> = (0..table.channels.len())
+        .filter(|i| match settings.chart_type {
+            ChartType::Pie => table.in_pie(*i),
+            ChartType::Heatmap => false,
+            _ => table.visible[*i],
+        })
+        .map(|i| (table.channels[i].clone(), table.colors[i]))
+        .collect();
+
+    let (w, h) = (width as f64, height as f64);
+    let draw = |cr: &cairo::Context| {
+        plots::clear(cr, w, h);
+        let legend_height = plots::legend_height(cr, &legend, w);
+        plot.draw(cr, w, h - legend_height);
+        plots::draw_legend(cr, &legend, h - legend_height, w);
+    };
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+    match extension.as_str() {
+        "png" => {
+            let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height)?;
+            draw(&cairo::Context::new(&surface));
+            surface.write_to_png(&mut File::create(path)?)?;
+        }
+        "svg" => {
+            let surface = cairo::SvgSurface::new(w, h, Some(path))?;
+            draw(&cairo::Context::new(&surface));
+            surface.finish();
+        }
+        "pdf" => {
+            let surface = cairo::PdfSurface::new(w, h, path)?;
+            draw(&cairo::Context::new(&surface));
+            surface.finish();
+        }
+        _ => return Err(format!("{} isn't a .png, .svg or .pdf file", path.display()).into()),
+    }
+    Ok(())
+}
+
+/// The chart on the charts page. The chart is made once and kept; updates only give it new
+/// data, then redraw.
+#[derive(Clone)]
+pub struct ChartView {
+    /// The chart, with its legend underneath.
+    pub widget: gtk::Box,
+    /// Shows the chart, or a message instead when there's nothing to chart yet.
+    pages: gtk::Stack,
+    pub drawing_area: gtk::DrawingArea,
+    /// A button per species: tapping one hides or shows it, holding one shows it alone.
+    legend: gtk::FlowBox,
+    /// Shown above the chart while it's showing the days of one bar, to go back.
+    drill_bar: gtk::Box,
+    drill_label: gtk::Label,
+    /// The exact values of the bar last tapped on.
+    tooltip: gtk::Popover,
+    tooltip_label: gtk::Label,
+    tapped: Rc<Cell<Option<(usize, usize)>>>,
+    chart: Rc<RefCell<Plot>>,
+    settings: Rc<Cell<ChartSettings>>,
+    drill: Rc<Cell<Option<Drill>>>,
+    data: Rc<RefCell<ChartData>>,
+    /// What the chart was last given to draw, to tell which bar is tapped on.
+    table: Rc<RefCell<Table>>,
+    /// The species hidden with the legend, kept in the "hidden_species" setting.
+    hidden: Rc<RefCell<BTreeSet<i64>>>,
+    /// Set when more than today's sightings may have changed, so the next refresh reads them all.
+    stale: Rc<Cell<bool>>,
+}
+
+impl ChartView {
+    pub fn new() -> ChartView {
+        let settings = ChartSettings::default();
+        let hidden = hidden_species();
+        let view = ChartView {
+            widget: gtk::Box::new(gtk::Orientation::Vertical, 5),
+            pages: gtk::Stack::new(),
+            drawing_area: gtk::DrawingArea::new(),
+            legend: gtk::FlowBox::new(),
+            drill_bar: gtk::Box::new(gtk::Orientation::Horizontal, 5),
+            drill_label: gtk::Label::new(None),
+            tooltip: gtk::Popover::new::<gtk::DrawingArea>(None),
+            tooltip_label: gtk::Label::new(None),
+            tapped: Rc::new(Cell::new(None)),
+            chart: Rc::new(RefCell::new(Plot::new(&settings, &[]))),
+            settings: Rc::new(Cell::new(settings)),
+            drill: Rc::new(Cell::new(None)),
+            data: Rc::new(RefCell::new(ChartData::default())),
+            table: Rc::new(RefCell::new(Table::default())),
+            hidden: Rc::new(RefCell::new(hidden)),
+            stale: Rc::new(Cell::new(false)),
+        };
+
+        let empty = gtk::Label::new(Some("No sightings yet.\nTap a bird's picture to log one, and it'll be charted here."));
+        empty.set_justify(gtk::Justification::Center);
+        view.pages.add_named(&view.drawing_area, "chart");
+        view.pages.add_named(&empty, "empty");
+        // Stack pages have to be visible before they can be switched to.
+        view.drawing_area.show();
+        empty.show();
+        view.legend.set_selection_mode(gtk::SelectionMode::None);
+        view.legend.set_max_children_per_line(8);
+        let back = gtk::Button::with_label("◀ Back");
+        let v = view.clone();
+        back.connect_clicked(move |_| v.set_drill(None));
+        view.drill_bar.pack_start(&back, false, false, 0);
+        view.drill_bar.pack_start(&view.drill_label, false, false, 0);
+        view.drill_bar.set_no_show_all(true);
+        back.show();
+        view.drill_label.show();
+        view.widget.pack_start(&view.drill_bar, false, false, 0);
+        view.widget.pack_start(&view.pages, true, true, 0);
+        view.widget.pack_start(&view.legend, false, false, 0);
+
+        view.tooltip.set_relative_to(Some(&view.drawing_area));
+        // A modal popover would swallow the second tap that shows the bar's days.
+        view.tooltip.set_modal(false);
+        view.tooltip.add(&view.tooltip_label);
+        view.tooltip_label.set_margin_top(5);
+        view.tooltip_label.set_margin_bottom(5);
+        view.tooltip_label.set_margin_start(5);
+        view.tooltip_label.set_margin_end(5);
+        view.tooltip_label.show();
+        view.drawing_area.add_events(gdk::EventMask::BUTTON_PRESS_MASK | gdk::EventMask::BUTTON_RELEASE_MASK | gdk::EventMask::TOUCH_MASK);
+        let tap = gtk::GestureMultiPress::new(&view.drawing_area);
+        let v = view.clone();
+        tap.connect_released(move |_gesture, _n_press, x, y| v.tapped_at(x, y));
+        unsafe { view.drawing_area.set_data("tap_gesture", tap); }
+
+        let chart = view.chart.clone();
+        view.drawing_area.connect_draw(move |area, cr| {
+            let (rect, _) = area.get_allocated_size();
+            chart.borrow().draw(cr, rect.width as f64, rect.height as f64);
+
+            Inhibit(false)
         });
+
+        view
+    }
+
+    pub fn settings(&self) -> ChartSettings {
+        self.settings.get()
+    }
+
+    /// What's being drawn: the settings picked, unless the chart is showing the days of one bar.
+    fn shown_settings(&self) -> ChartSettings {
+        let settings = self.settings.get();
+        match self.drill.get() {
+            Some(drill) => ChartSettings {
+                chart_type: ChartType::Bar,
+                granularity: Granularity::Day,
+                drill: Some(drill),
+                ..settings
+            },
+            None => settings,
+        }
+    }
+
+    /// Changes what's shown, going back from any bar's days. The chart is only made again if
+    /// its title or axes change with it.
+    pub fn set_settings(&self, settings: ChartSettings) {
+        if settings == self.settings.get() {
+            return;
+        }
+        let previous = self.shown_settings();
+        self.settings.set(settings);
+        self.drill.set(None);
+        self.drill_bar.hide();
+        self.remake_chart(previous);
+        if settings.observer_id != previous.observer_id {
+            self.update();
+        } else {
+            self.redraw();
+        }
+    }
+
+    fn remake_chart(&self, previous: ChartSettings) {
+        let settings = self.shown_settings();
+        if settings.chart_type != previous.chart_type
+            || settings.metric != previous.metric
+            || settings.granularity != previous.granularity {
+            let colors = self.chart.borrow().colors().to_vec();
+            *self.chart.borrow_mut() = Plot::new(&settings, &colors);
+        }
+    }
+
+    /// Shows the days of one bar, or goes back to the chart picked with `None`.
+    fn set_drill(&self, drill: Option<Drill>) {
+        let previous = self.shown_settings();
+        self.drill.set(drill);
+        self.drill_bar.set_visible(drill.is_some());
+        self.remake_chart(previous);
+        self.redraw();
+    }
+
+    /// Saves the chart as it's shown, see `export_chart`.
+    pub fn export(&self, path: &Path, width: i32, height: i32) -> Result<(), Box<dyn Error>> {
+        export_chart(&self.shown_settings(), path, width, height)
+    }
+
+    /// Shows the exact values of the bar tapped on, or its days if it's tapped on again.
+    fn tapped_at(&self, x: f64, y: f64) {
+        let settings = self.shown_settings();
+        let (width, height) = (self.drawing_area.get_allocated_width(), self.drawing_area.get_allocated_height());
+        let table = self.table.borrow();
+        let hit = self.chart.borrow().bar_at(x, y, width as f64, height as f64);
+        let (channel, period) = match hit {
+            Some(hit) => hit,
+            None => {
+                self.tooltip.hide();
+                self.tapped.set(None);
+                return;
+            }
+        };
+        let (first_day, last_day) = period_days(&table, period, settings.granularity);
+        let dates = date_range(first_day, last_day);
+
+        if self.tapped.get() == hit && self.tooltip.is_visible() && settings.granularity != Granularity::Day {
+            self.tooltip.hide();
+            self.tapped.set(None);
+            self.drill_label.set_text(&format!("{}, {}", table.channels[channel], dates));
+            let drill = Drill {
+                animal_id: table.animal_ids[channel],
+                site_id: table.site_ids[channel],
+                first_day,
+                last_day,
+            };
+            drop(table);
+            self.set_drill(Some(drill));
+            return;
+        }
+
+        let (days, birds) = (table.days[channel][period], table.birds[channel][period]);
+        let mut text = format!(
+            "<b>{}</b>\n{}\n{}",
+            glib::markup_escape_text(&table.channels[channel]),
+            dates,
+            match (days, birds) {
+                (1, 1) => String::from("Seen on 1 day, 1 bird"),
+                (1, birds) => format!("Seen on 1 day, {} birds", birds),
+                (days, birds) => format!("Seen on {} days, {} birds in all", days, birds),
+            },
+        );
+        if settings.metric == Metric::MaxIndividuals && days > 1 {
+            text.push_str(&format!(", at most {} in a day", table.values[channel][period]));
+        }
+        if settings.granularity != Granularity::Day {
+            text.push_str("\n<i>Tap again for each day</i>");
+        }
+        self.tooltip_label.set_markup(&text);
+        self.tooltip.set_pointing_to(&gdk::Rectangle { x: x as i32, y: y as i32, width: 1, height: 1 });
+        self.tooltip.show();
+        self.tapped.set(hit);
+    }
+
+    /// Reads the chart's data again and redraws it.
+    pub fn update(&self) {
+        self.stale.set(false);
+        match ChartData::load(self.settings.get().observer_id) {
+            Ok(data) => *self.data.borrow_mut() = data,
+            Err(error) => notify(Severity::Error, &format!("Error reading sightings for the chart - {}", error)),
+        }
+        self.redraw();
+    }
+
+    /// Makes the next `refresh` read every sighting again, e.g. after an old one is restored.
+    pub fn invalidate(&self) {
+        self.stale.set(true);
     }
 
-    let mut sdata: Vec<Vec<u32>> = vec![vec![0; weeks.len() as usize]; metadata.len()];
+    /// Catches up with sightings logged elsewhere, which are only ever for today, and with any
+    /// new species or day. Reads everything again if the chart's been invalidated.
+    pub fn refresh(&self) {
+        if self.stale.get() {
+            return self.update();
+        }
+        let result = self.data.borrow_mut().reload_today();
+        if let Err(error) = result {
+            notify(Severity::Error, &format!("Error reading sightings for the chart - {}", error));
+        }
+        self.redraw();
+    }
+
+    /// Reads just today's totals for a species again, after one of its sightings was logged or cleared.
+    pub fn sighting_changed(&self, animal_id: i64) {
+        let today = Local::now().format("%Y-%m-%d").to_string();
+        let result = self.data.borrow_mut().reload_day(animal_id, &today);
+        if let Err(error) = result {
+            notify(Severity::Error, &format!("Error reading sightings for the chart - {}", error));
+        }
+        self.redraw();
+    }
+
+    /// Builds the chart's data from what's already been read, and redraws it once.
+    fn redraw(&self) {
+        let settings = self.shown_settings();
+        let data = self.data.borrow();
+        self.pages.set_visible_child_name(if data.totals.is_empty() { "empty" } else { "chart" });
+        let table = chart_table(&data, &settings, &self.hidden.borrow());
+        self.chart.borrow_mut().set_data(&data, &settings, &table);
+        // Split by site, each species has a channel and a shade per site.
+        let legend: Vec<(i64, String, Rgb)> = match settings.site_filter {
+            SiteFilter::Split => (0..table.channels.len())
+                .map(|i| (table.animal_ids[i], table.channels[i].clone(), table.colors[i]))
+                .collect(),
+            _ => data.seen_species()
+                .map(|species| (
+                    species.animal_id.unwrap_or(0),
+                    species.name.clone().unwrap_or_default(),
+                    species.color.as_deref().and_then(colors::parse).unwrap_or(colors::UNASSIGNED),
+                ))
+                .collect(),
+        };
+        *self.table.borrow_mut() = table;
+        // The bars may have moved out from under the tooltip.
+        self.tooltip.hide();
+        self.tapped.set(None);
+        self.drawing_area.queue_draw();
+        self.populate_legend(&legend, settings.chart_type != ChartType::Heatmap && settings.drill.is_none());
+    }
+
+    /// Makes a legend button for every species that's been seen, or for every channel when split by
+    /// site. Each button hides or shows its species.
+    fn populate_legend(&self, legend: &[(i64, String, Rgb)], shown: bool) {
+        self.legend.foreach(|child| self.legend.remove(child));
+        self.legend.set_visible(shown);
+        let hidden = self.hidden.borrow();
+        for &(animal_id, ref name, color) in legend {
+            let label = gtk::Label::new(None);
+            label.set_markup(&format!(
+                "<span foreground=\"{}\">●</span> {}",
+                colors::to_hex(color),
+                glib::markup_escape_text(name),
+            ));
+            let button = gtk::Button::new();
+            button.add(&label);
+            button.set_relief(gtk::ReliefStyle::None);
+            button.set_opacity(if hidden.contains(&animal_id) { 0.4 } else { 1.0 });
 
-    for (i, channel) in metadata.iter().enumerate() {
-        let sightings = get_sightings(channel.tag as u32);
-        for sighting in sightings {
-            if let Some(week) = sighting.week {
-                let week_num = week.parse::<u32>().unwrap();
-                let j: usize = (week_num - weeks.first().unwrap()) as usize;
-                sdata[i][j] = sighting.count.unwrap_or(0);
+            let view = self.clone();
+            button.connect_clicked(move |button| {
+                // Letting go of a long press shouldn't also hide the species again.
+                if unsafe { button.steal_data::<bool>("soloed") }.is_none() {
+                    view.toggle_species(animal_id);
+                }
+            });
+            let gesture = gtk::GestureLongPress::new(&button);
+            gesture.set_propagation_phase(gtk::PropagationPhase::Capture);
+            let view = self.clone();
+            gesture.connect_pressed(move |gesture, _x, _y| {
+                if let Some(widget) = gesture.get_widget() {
+                    unsafe { widget.set_data("soloed", true) };
+                }
+                view.solo_species(animal_id);
+            });
+            unsafe { button.set_data("gesture", gesture); }
+            self.legend.add(&button);
+        }
+        if shown {
+            self.legend.show_all();
+        }
+    }
+
+    fn toggle_species(&self, animal_id: i64) {
+        {
+            let mut hidden = self.hidden.borrow_mut();
+            if !hidden.remove(&animal_id) {
+                hidden.insert(animal_id);
             }
         }
+        self.hidden_changed();
     }
 
-    let mut frames = Vec::new();
-    for (i, _weeknum) in weeks.iter().enumerate() {
-        let mut imap: IntMap<i32> = IntMap::with_capacity(metadata.len());
-        for bnum in 0..metadata.len() {
-            imap.insert(bnum as u64, sdata[bnum][i] as i32);
+    /// Hides every species but one, or shows them all again if it's already the only one shown.
+    fn solo_species(&self, animal_id: i64) {
+        let others: BTreeSet<i64> = self.data.borrow().seen_species()
+            .filter_map(|species| species.animal_id)
+            .filter(|id| *id != animal_id)
+            .collect();
+        {
+            let mut hidden = self.hidden.borrow_mut();
+            *hidden = if others.is_subset(&hidden) && !hidden.contains(&animal_id) { BTreeSet::new() } else { others };
         }
-        frames.push(DataFrame {
-           metric: week_labels[i].clone(),
-           data: imap
+        self.hidden_changed();
+    }
+
+    fn hidden_changed(&self) {
+        let ids: Vec<String> = self.hidden.borrow().iter().map(|id| id.to_string()).collect();
+        if let Err(error) = crate::set_setting("hidden_species", &ids.join(",")) {
+            notify(Severity::Error, &format!("Error saving which species are hidden - {}", error));
+        }
+        // Redrawing rebuilds the legend, so wait until the button that was pressed is done with.
+        let view = self.clone();
+        glib::idle_add_local(move || {
+            view.redraw();
+            glib::Continue(false)
         });
     }
+}
 
-    DataStream::new(metadata, frames)
+fn fills(colors: &[Rgb]) -> Vec<Fill> {
+    colors.iter().map(|(r, g, b)| Fill::Solid(Color::rgb(*r, *g, *b))).collect()
 }
 
-pub fn update_chart(drawing_area: &gtk::DrawingArea) -> () {
-    let stream = create_stream();
-    let mut options: BarChartOptions = Default::default();
-    options.channel.labels = Some(Default::default());
+fn line_options(settings: &ChartSettings, colors: &[Rgb]) -> LineChartOptions {
+    let mut options: LineChartOptions = Default::default();
     options.yaxis.min_interval = Some(1.);
-    options.title.text = Some("Weekly Bird Sightings".to_string());
-    options.xaxis.title.text = Some("Weeks Ago".to_string());
-    options.legend.position = Position::Top;
-    options.legend.label_formatter = Some(charts::default_label_formatter);
-    options.legend.style = Default::default();
-    // These colours are from the OpenOffice charts palette.
-    options.colors = vec![
-        Fill::Solid(Color::rgb(0x00, 0x45, 0x86)),
-        Fill::Solid(Color::rgb(0xff, 0x42, 0x0e)),
-        Fill::Solid(Color::rgb(0xff, 0xd3, 0x20)),
-        Fill::Solid(Color::rgb(0x57, 0x9d, 0x1c)),
-        Fill::Solid(Color::rgb(0x7e, 0x00, 0x21)),
-        Fill::Solid(Color::rgb(0x83, 0xca, 0xff)),
-        Fill::Solid(Color::rgb(0x31, 0x40, 0x04)),
-        Fill::Solid(Color::rgb(0xae, 0xcf, 0x00))
-    ];
-
-    let mut chart = BarChart::new(options);
-    chart.set_stream(stream);
-    
-    drawing_area.connect_draw(move |area, cr| {
-        let (rect, _) = area.get_allocated_size();
-        let size = (rect.width as f64, rect.height as f64);
-
-        chart.resize(size.0, size.1);
-
-        let ctx = Canvas::new(cr); // overhead
-        chart.draw(&ctx);
-
-        Inhibit(false)
-    });
-}
\ No newline at end of file
+    options.title.text = Some(settings.metric.title(settings.granularity));
+    options.xaxis.title.text = Some(settings.granularity.axis_title().to_string());
+    options.legend.position = Position::None;
+    options.colors = fills(colors);
+    options
+}
+
+fn pie_options(colors: &[Rgb]) -> PieChartOptions {
+    let mut options: PieChartOptions = Default::default();
+    options.title.text = Some("Share of Days Seen".to_string());
+    options.legend.position = Position::None;
+    options.colors = fills(colors);
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(year, month, day)
+    }
+
+    #[test]
+    fn weeks_start_on_monday_across_the_new_year() {
+        // 28 December 2020 is a Monday, and its week runs into 2021.
+        assert_eq!(week_start(day(2020, 12, 28)), day(2020, 12, 28));
+        assert_eq!(week_start(day(2020, 12, 31)), day(2020, 12, 28));
+        assert_eq!(week_start(day(2021, 1, 3)), day(2020, 12, 28));
+        assert_eq!(week_start(day(2021, 1, 4)), day(2021, 1, 4));
+        assert_eq!(Granularity::Week.start(day(2021, 1, 1)), day(2020, 12, 28));
+        assert_eq!(Granularity::Week.next(day(2020, 12, 28)), day(2021, 1, 4));
+    }
+
+    #[test]
+    fn periods_start_and_roll_over() {
+        assert_eq!(Granularity::Day.start(day(2021, 3, 15)), day(2021, 3, 15));
+        assert_eq!(Granularity::Day.next(day(2020, 12, 31)), day(2021, 1, 1));
+        assert_eq!(Granularity::Month.start(day(2021, 3, 15)), day(2021, 3, 1));
+        assert_eq!(Granularity::Month.next(day(2021, 1, 1)), day(2021, 2, 1));
+        assert_eq!(Granularity::Month.next(day(2020, 12, 1)), day(2021, 1, 1));
+        assert_eq!(Granularity::Year.start(day(2021, 3, 15)), day(2021, 1, 1));
+        assert_eq!(Granularity::Year.next(day(2020, 1, 1)), day(2021, 1, 1));
+    }
+
+    #[test]
+    fn labels_add_the_year_when_needed() {
+        let start = day(2020, 12, 28);
+        assert_eq!(Granularity::Week.label_for(start, false), "28 Dec");
+        assert_eq!(Granularity::Week.label_for(start, true), "28 Dec 2020");
+        assert_eq!(Granularity::Day.label_for(day(2021, 1, 4), false), "4 Jan");
+        assert_eq!(Granularity::Month.label_for(day(2021, 1, 1), false), "Jan");
+        assert_eq!(Granularity::Month.label_for(day(2021, 1, 1), true), "Jan 2021");
+        assert_eq!(Granularity::Year.label_for(day(2021, 1, 1), false), "2021");
+    }
+
+    #[test]
+    fn ranges_count_back_from_today() {
+        // 2020 is a leap year, so 30 days back from 1 March lands on 1 February.
+        assert_eq!(Range::Last30Days.first_day(day(2020, 3, 1)), Some(day(2020, 2, 1)));
+        assert_eq!(Range::Last12Months.first_day(day(2021, 6, 15)), Some(day(2020, 6, 16)));
+        assert_eq!(Range::ThisYear.first_day(day(2021, 6, 15)), Some(day(2021, 1, 1)));
+        assert_eq!(Range::AllTime.first_day(day(2021, 6, 15)), None);
+    }
+}
@@ -0,0 +1,292 @@
+//! Charts drawn straight onto the cairo context in a similar style to ux-charts: the ones it doesn't
+//! have, and bars, so that which one was tapped on can be told exactly.
+
+use super::{week_start, Table};
+use crate::colors::{self, Rgb};
+use chrono::prelude::*;
+use chrono::Duration;
+use std::collections::HashMap;
+
+const MARGIN: f64 = 20.0;
+const TITLE_HEIGHT: f64 = 40.0;
+const AXIS_WIDTH: f64 = 50.0;
+const AXIS_HEIGHT: f64 = 50.0;
+const LEGEND_ROW_HEIGHT: f64 = 22.0;
+
+fn set_color(cr: &cairo::Context, (r, g, b): Rgb) {
+    cr.set_source_rgb(r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0);
+}
+
+pub(super) fn clear(cr: &cairo::Context, width: f64, height: f64) {
+    cr.set_source_rgb(1.0, 1.0, 1.0);
+    cr.rectangle(0.0, 0.0, width, height);
+    cr.fill();
+}
+
+fn draw_title(cr: &cairo::Context, width: f64, title: &str) {
+    cr.select_font_face("Sans", cairo::FontSlant::Normal, cairo::FontWeight::Bold);
+    cr.set_font_size(18.0);
+    let extents = cr.text_extents(title);
+    cr.set_source_rgb(0.2, 0.2, 0.2);
+    cr.move_to((width - extents.width) / 2.0, MARGIN + extents.height);
+    cr.show_text(title);
+    cr.select_font_face("Sans", cairo::FontSlant::Normal, cairo::FontWeight::Normal);
+    cr.set_font_size(12.0);
+}
+
+/// Draws `text` centred on `x`, with its baseline at `y`.
+fn show_centred(cr: &cairo::Context, text: &str, x: f64, y: f64) {
+    let extents = cr.text_extents(text);
+    cr.move_to(x - extents.width / 2.0, y);
+    cr.show_text(text);
+}
+
+/// Where each legend entry goes, as an offset from the legend's top left, wrapping onto more
+/// rows to fit `width`.
+fn legend_layout(cr: &cairo::Context, entries: &[(String, Rgb)], width: f64) -> Vec<(f64, f64)> {
+    let (mut x, mut y) = (MARGIN, 0.0);
+    entries.iter().map(|(name, _)| {
+        let entry_width = cr.text_extents(name).x_advance + 30.0;
+        if x + entry_width > width - MARGIN && x > MARGIN {
+            x = MARGIN;
+            y += LEGEND_ROW_HEIGHT;
+        }
+        let at = (x, y);
+        x += entry_width;
+        at
+    }).collect()
+}
+
+/// How tall `draw_legend` will draw the legend.
+pub(super) fn legend_height(cr: &cairo::Context, entries: &[(String, Rgb)], width: f64) -> f64 {
+    match legend_layout(cr, entries, width).last() {
+        Some((_, y)) => y + LEGEND_ROW_HEIGHT + MARGIN,
+        None => 0.0,
+    }
+}
+
+/// Draws a swatch and name for each entry, starting at `top`. The chart page uses buttons
+/// instead, so this is only for exported charts.
+pub(super) fn draw_legend(cr: &cairo::Context, entries: &[(String, Rgb)], top: f64, width: f64) {
+    cr.set_font_size(12.0);
+    for ((name, color), (x, y)) in entries.iter().zip(legend_layout(cr, entries, width)) {
+        set_color(cr, *color);
+        cr.rectangle(x, top + y + 4.0, 12.0, 12.0);
+        cr.fill();
+        cr.set_source_rgb(0.2, 0.2, 0.2);
+        cr.move_to(x + 16.0, top + y + 15.0);
+        cr.show_text(name);
+    }
+}
+
+/// Where the bars go, as left, top, width and height, leaving room for the title and axes.
+fn plot_area(width: f64, height: f64) -> (f64, f64, f64, f64) {
+    let left = MARGIN + AXIS_WIDTH;
+    let top = MARGIN + TITLE_HEIGHT;
+    (left, top, width - left - MARGIN, height - top - MARGIN - AXIS_HEIGHT)
+}
+
+/// A step between axis ticks that's a round number, giving about five ticks up to `max`.
+fn tick_step(max: f64) -> f64 {
+    let rough = (max / 5.0).max(1.0);
+    let magnitude = 10f64.powf(rough.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0].iter()
+        .map(|m| m * magnitude)
+        .find(|step| *step >= rough)
+        .unwrap_or(10.0 * magnitude);
+    step.max(1.0)
+}
+
+/// Bars for each period, with a bar per channel side by side, or a segment per channel stacked
+/// on top of each other.
+#[derive(Debug, Default)]
+pub(super) struct Bars {
+    pub(super) title: String,
+    pub(super) axis_title: String,
+    pub(super) stacked: bool,
+    pub(super) table: Table,
+}
+
+impl Bars {
+    fn color(&self, channel: usize) -> Rgb {
+        self.table.colors.get(channel).copied().unwrap_or(colors::UNASSIGNED)
+    }
+
+    /// The rows of the channels that haven't been hidden.
+    fn shown_rows(&self) -> Vec<(usize, &Vec<i32>)> {
+        self.table.values.iter().enumerate()
+            .filter(|(i, _)| self.table.visible[*i])
+            .collect()
+    }
+
+    /// The step between ticks on the value axis, and the value at its top.
+    fn scale(&self) -> (f64, f64) {
+        let rows = self.shown_rows();
+        let tallest = (0..self.table.labels.len())
+            .map(|j| {
+                let values = rows.iter().map(|(_, row)| row[j]);
+                if self.stacked { values.sum::<i32>() } else { values.max().unwrap_or(0) }
+            })
+            .max()
+            .unwrap_or(0) as f64;
+        let step = tick_step(tallest);
+        (step, (tallest / step).ceil().max(1.0) * step)
+    }
+
+    /// Where each channel's bar (or segment) in period `j` is drawn, as the channel, left,
+    /// bottom value, width and value.
+    fn bars(&self, j: usize, left: f64, slot: f64) -> Vec<(usize, f64, f64, f64, f64)> {
+        let rows = self.shown_rows();
+        let (x, width) = (left + slot * j as f64 + slot * 0.15, slot * 0.7);
+        let mut base = 0.0;
+        let mut bars = Vec::new();
+        for (k, (i, row)) in rows.iter().enumerate() {
+            let value = row[j] as f64;
+            if self.stacked {
+                if value > 0.0 {
+                    bars.push((*i, x, base, width, value));
+                    base += value;
+                }
+            } else {
+                let bar_width = width / rows.len() as f64;
+                if value > 0.0 {
+                    bars.push((*i, x + bar_width * k as f64, 0.0, bar_width, value));
+                }
+            }
+        }
+        bars
+    }
+
+    /// The channel and period of the bar (or segment) at a point.
+    pub(super) fn bar_at(&self, x: f64, y: f64, width: f64, height: f64) -> Option<(usize, usize)> {
+        let (left, top, plot_width, plot_height) = plot_area(width, height);
+        let periods = self.table.labels.len();
+        if periods == 0 || x < left || x >= left + plot_width {
+            return None;
+        }
+        let slot = plot_width / periods as f64;
+        let j = ((x - left) / slot) as usize;
+        let (_, max) = self.scale();
+        let value = (top + plot_height - y) / plot_height * max;
+        self.bars(j, left, slot).into_iter()
+            .find(|(_, bar_x, base, bar_width, bar_value)| {
+                x >= *bar_x && x < bar_x + bar_width && value >= *base && value < base + bar_value
+            })
+            .map(|(i, ..)| (i, j))
+    }
+
+    pub(super) fn draw(&self, cr: &cairo::Context, width: f64, height: f64) {
+        clear(cr, width, height);
+        draw_title(cr, width, &self.title);
+
+        let (left, top, plot_width, plot_height) = plot_area(width, height);
+        let periods = self.table.labels.len();
+        if periods == 0 || plot_width <= 0.0 || plot_height <= 0.0 {
+            return;
+        }
+
+        let (step, max) = self.scale();
+        let y_for = |value: f64| top + plot_height - value / max * plot_height;
+
+        // Value axis, with grid lines.
+        cr.set_line_width(1.0);
+        let mut tick = 0.0;
+        while tick <= max {
+            cr.set_source_rgb(0.85, 0.85, 0.85);
+            cr.move_to(left, y_for(tick).round() + 0.5);
+            cr.line_to(left + plot_width, y_for(tick).round() + 0.5);
+            cr.stroke();
+            cr.set_source_rgb(0.3, 0.3, 0.3);
+            let text = tick.to_string();
+            let extents = cr.text_extents(&text);
+            cr.move_to(left - extents.width - 6.0, y_for(tick) + extents.height / 2.0);
+            cr.show_text(&text);
+            tick += step;
+        }
+
+        // The bars, and as many period labels as fit without overlapping.
+        let slot = plot_width / periods as f64;
+        let label_every = ((60.0 / slot).ceil() as usize).max(1);
+        for j in 0..periods {
+            for (i, x, base, bar_width, value) in self.bars(j, left, slot) {
+                set_color(cr, self.color(i));
+                cr.rectangle(x, y_for(base + value), bar_width, y_for(base) - y_for(base + value));
+                cr.fill();
+            }
+            if j % label_every == 0 {
+                cr.set_source_rgb(0.3, 0.3, 0.3);
+                show_centred(cr, &self.table.labels[j], left + slot * (j as f64 + 0.5), top + plot_height + 18.0);
+            }
+        }
+        show_centred(cr, &self.axis_title, left + plot_width / 2.0, height - MARGIN);
+    }
+}
+
+/// A calendar with a square per day, shaded in the species' colour by how many were seen, in rows
+/// from Monday to Sunday and a column per week.
+#[derive(Debug)]
+pub(super) struct Heatmap {
+    pub(super) title: String,
+    pub(super) color: Rgb,
+    pub(super) first_day: NaiveDate,
+    pub(super) last_day: NaiveDate,
+    pub(super) values: HashMap<NaiveDate, i64>,
+}
+
+impl Default for Heatmap {
+    fn default() -> Heatmap {
+        let today = Local::today().naive_local();
+        Heatmap { title: String::new(), color: colors::UNASSIGNED, first_day: today, last_day: today, values: HashMap::new() }
+    }
+}
+
+impl Heatmap {
+    pub(super) fn draw(&self, cr: &cairo::Context, width: f64, height: f64) {
+        clear(cr, width, height);
+        draw_title(cr, width, &self.title);
+
+        let first_week = week_start(self.first_day);
+        let weeks = ((week_start(self.last_day) - first_week).num_days() / 7 + 1) as f64;
+        let left = MARGIN + 40.0;
+        let top = MARGIN + TITLE_HEIGHT + 20.0;
+        let cell = ((width - left - MARGIN) / weeks).min((height - top - MARGIN - 30.0) / 7.0).min(40.0);
+        if cell < 2.0 {
+            return;
+        }
+        let gap = (cell * 0.1).max(1.0);
+        let max = self.values.values().copied().max().unwrap_or(0).max(1) as f64;
+
+        cr.set_source_rgb(0.3, 0.3, 0.3);
+        for (row, name) in ["Mon", "", "Wed", "", "Fri", "", "Sun"].iter().enumerate() {
+            cr.move_to(MARGIN, top + cell * (row as f64 + 0.7));
+            cr.show_text(name);
+        }
+
+        let mut day = self.first_day;
+        while day <= self.last_day {
+            let column = ((week_start(day) - first_week).num_days() / 7) as f64;
+            let row = day.weekday().num_days_from_monday() as f64;
+            let (x, y) = (left + column * cell, top + row * cell);
+            // Month names go above the week each month starts in.
+            if day.day() == 1 || day == self.first_day {
+                cr.set_source_rgb(0.3, 0.3, 0.3);
+                cr.move_to(x, top - 6.0);
+                cr.show_text(&day.format("%b").to_string());
+            }
+            match self.values.get(&day) {
+                Some(value) if *value > 0 => {
+                    // From pale to the full colour as the count goes up.
+                    set_color(cr, colors::lighten(self.color, 0.75 * (1.0 - *value as f64 / max)));
+                }
+                _ => cr.set_source_rgb(0.92, 0.92, 0.92),
+            }
+            cr.rectangle(x, y, cell - gap, cell - gap);
+            cr.fill();
+            day = day + Duration::days(1);
+        }
+
+        cr.set_source_rgb(0.3, 0.3, 0.3);
+        cr.move_to(left, top + cell * 7.0 + 20.0);
+        cr.show_text(&format!("Darkest: {}", max));
+    }
+}
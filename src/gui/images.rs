@@ -4,85 +4,191 @@ extern crate cairo;
 
 use crate::Animal;
 use crate::gui;
-use crate::charts;
+use crate::gui::cache;
+use crate::gui::notices::{notify, Severity};
 use gtk::prelude::*;
 use gdk::prelude::*;
 use gtk::{
-    Box, Entry, EventBox, FileChooserAction, FileChooserDialog, FileFilter,
-    Orientation, ResponseType, Window, DrawingArea, GestureLongPress
+    Box, Button, Entry, EntryCompletion, EventBox, FileChooserAction, FileChooserDialog, FileFilter,
+    ListStore, Orientation, ResponseType, Window, DrawingArea, GestureLongPress, Popover, SpinButton
 };
 use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
-use turbosql::select;
+use turbosql::{Blob, execute, select};
+
+/// Longest edge of the copy of a species image that is kept in the database.
+const STORED_IMAGE_SIZE: i32 = 480;
+/// The size tiles are laid out at when the grid is fitted to the window.
+const TILE_SIZE: i32 = 180;
+/// Tiles can shrink to this so the window can still be made smaller than the grid.
+const MIN_TILE_SIZE: i32 = 90;
+/// Used until the window has a size, and if the `grid` setting can't be read.
+const DEFAULT_GRID: (usize, usize) = (4, 2);
 
 impl gui::Gui {
 
-    pub fn load_images(&self, animals: &Vec<Animal>) {
-        for y in 0..2 {
+    /// Lays the species out in a grid sized to fit the window (or as set with `--grid`), a page
+    /// at a time. The last tile of every page adds a new species, so it's always in reach.
+    pub fn load_images(&self) {
+        self.images.foreach(|child| self.images.remove(child));
+
+        let animals = ordered_animals();
+        let (columns, rows) = self.grid_size.get();
+        let per_page = columns * rows - 1;
+        let pages = ((animals.len() + per_page - 1) / per_page).max(1);
+        let page = self.page.get().min(pages - 1);
+        self.page.set(page);
+        let shown: Vec<&Animal> = animals.iter().skip(page * per_page).take(per_page).collect();
+
+        for y in 0..rows {
             let hbox = Box::new(Orientation::Horizontal, 5);
             hbox.set_homogeneous(true);
 
             self.images.add(&hbox);
-            for i in 0..4 {
-                let animal_r = animals.get((y * 4) + i);
-                let img = DrawingArea::new();
-                img.set_size_request(180, 180);
-                img.connect_draw(draw_image);
-                let ebox = EventBox::new();
-                unsafe {
-                    ebox.set_data(
-                        "animal",
-                        match animal_r {
-                            Some(animal) => animal.rowid.unwrap_or(0),
-                            None => 0,
-                        },
-                    );
+            for x in 0..columns {
+                let slot = (y * columns) + x;
+                if slot == per_page {
+                    self.add_tile(&hbox, 0);
+                } else if let Some(animal) = shown.get(slot) {
+                    self.add_tile(&hbox, animal.rowid.unwrap_or(0));
+                } else {
+                    hbox.pack_start(&gtk::Label::new(None), true, true, 0);
                 }
-                ebox.add(&img);
-                ebox.set_above_child(true);
-                let drawing_area = self.drawing_area.clone();
-                ebox.connect_event(move |widget, event| {
-                    let event_type = event.get_event_type();
-                    if event_type == gdk::EventType::ButtonPress || event_type == gdk::EventType::TouchBegin {
-                        let coords = event.get_coords().unwrap_or((0.0, 0.0));
-                        unsafe { widget.set_data("last_coords", (coords.0, coords.1, event.get_time())) };
-                    } else if event_type == gdk::EventType::ButtonRelease || event_type == gdk::EventType::TouchEnd {
-                        if let Some(last_coords) = unsafe { widget.get_data::<(f64, f64, u32)>("last_coords") } {
-                            let coords = event.get_coords().unwrap_or((0.0, 0.0));
-                            if (last_coords.0 == coords.0) && (last_coords.1 == coords.1) && (event.get_time() - last_coords.2 < 500) {
-                                animal_selected(widget);
-                                charts::update_chart(&drawing_area);
-                            }
+            }
+        }
+
+        self.page_label.set_text(&format!("{} / {}", page + 1, pages));
+        self.previous_page.set_sensitive(page > 0);
+        self.next_page.set_sensitive(page + 1 < pages);
+        for widget in &[self.page_label.upcast_ref::<gtk::Widget>(), self.previous_page.upcast_ref(), self.next_page.upcast_ref()] {
+            widget.set_no_show_all(pages == 1);
+            widget.set_visible(pages > 1);
+        }
+        self.images.show_all();
+    }
+
+    /// Moves `offset` pages forwards or backwards through the species.
+    pub fn turn_page(&self, offset: isize) {
+        let page = self.page.get() as isize + offset;
+        self.page.set(page.max(0) as usize);
+        self.load_images();
+    }
+
+    /// Re-lays the tiles if the window has been resized to fit a different number of them.
+    pub fn fit_grid(&self) {
+        let size = grid_size(self.images.get_allocated_width(), self.images.get_allocated_height());
+        if size != self.grid_size.get() {
+            // Keep the first species on the current page in view.
+            let (columns, rows) = self.grid_size.get();
+            let first = self.page.get() * (columns * rows - 1);
+            self.page.set(first / (size.0 * size.1 - 1));
+            self.grid_size.set(size);
+            self.load_images();
+        }
+    }
+
+    fn add_tile(&self, hbox: &Box, animal_id: i64) {
+        let img = DrawingArea::new();
+        img.set_size_request(MIN_TILE_SIZE, MIN_TILE_SIZE);
+        img.connect_draw(draw_image);
+        let ebox = EventBox::new();
+        unsafe {
+            ebox.set_data("animal", animal_id);
+        }
+        ebox.add(&img);
+        ebox.set_above_child(true);
+        let gui = self.clone();
+        ebox.connect_event(move |widget, event| {
+            let event_type = event.get_event_type();
+            if event_type == gdk::EventType::ButtonPress || event_type == gdk::EventType::TouchBegin {
+                let coords = event.get_coords().unwrap_or((0.0, 0.0));
+                unsafe { widget.set_data("last_coords", (coords.0, coords.1, event.get_time())) };
+            } else if event_type == gdk::EventType::ButtonRelease || event_type == gdk::EventType::TouchEnd {
+                if unsafe { widget.steal_data::<bool>("dragging") }.is_some() {
+                    widget.set_opacity(1.0);
+                    let (x, y) = event.get_coords().unwrap_or((0.0, 0.0));
+                    let (x_root, y_root) = event.get_root_coords().unwrap_or((0.0, 0.0));
+                    match gui.tile_at(x_root, y_root) {
+                        Some(target) if &target != widget => gui.move_tile(widget, &target),
+                        Some(_) => show_tally_popover(&gui, widget, x, y),
+                        None => (),
+                    }
+                } else if let Some(last_coords) = unsafe { widget.get_data::<(f64, f64, u32)>("last_coords") } {
+                    let coords = event.get_coords().unwrap_or((0.0, 0.0));
+                    if (last_coords.0 == coords.0) && (last_coords.1 == coords.1) && (event.get_time() - last_coords.2 < 500) {
+                        animal_selected(&gui, widget);
+                        match unsafe { widget.get_data::<i64>("animal") } {
+                            Some(animal_id) if *animal_id != 0 => gui.chart.sighting_changed(*animal_id),
+                            _ => gui.chart.update(),
                         }
                     }
-                    Inhibit(false)
-                });
-                hbox.pack_start(&ebox, true, true, 0);
-                
-                // Add long press gesture to clear selection.
-                let gesture = GestureLongPress::new(&ebox);
-                gesture.set_propagation_phase(gtk::PropagationPhase::Capture);
-                let drawing_area = self.drawing_area.clone();
-                gesture.connect_pressed(move |gesture, _x, _y| {
-                    if let Some(widget) = gesture.get_widget() {
-                        if let Ok(eventbox) = widget.downcast::<gtk::EventBox>() {
-                            if let Some(animal_id) = unsafe { eventbox.get_data::<i64>("animal") } {
-                                crate::clear_sighting(*animal_id);
-                                charts::update_chart(&drawing_area);
-                            }
-                            if let Some(da) = eventbox.get_child() {
-                                da.queue_draw();
-                            }
+                }
+            }
+            Inhibit(false)
+        });
+        hbox.pack_start(&ebox, true, true, 0);
+
+        // Add long press gesture to pick the tile up. Letting go where it was shows the popover
+        // to log a flock or clear the day's sightings; letting go over another tile moves it there.
+        if animal_id != 0 {
+            let gesture = GestureLongPress::new(&ebox);
+            gesture.set_propagation_phase(gtk::PropagationPhase::Capture);
+            let gui = self.clone();
+            gesture.connect_pressed(move |gesture, _x, _y| {
+                if let Some(widget) = gesture.get_widget() {
+                    unsafe { widget.set_data("dragging", true) };
+                    widget.set_opacity(0.6);
+                    // Stop the drag being taken as a swipe to the charts.
+                    if let Some(pan) = unsafe { gui.stack.get_data::<gtk::GesturePan>("pan_gesture") } {
+                        pan.set_state(gtk::EventSequenceState::Denied);
+                    }
+                }
+            });
+            unsafe { ebox.set_data("gesture", gesture); }
+        }
+    }
+
+    /// The species tile under a point on the screen.
+    fn tile_at(&self, x_root: f64, y_root: f64) -> Option<EventBox> {
+        let (origin_x, origin_y) = self.images.get_window()?.get_origin();
+        let (x, y) = (x_root as i32 - origin_x, y_root as i32 - origin_y);
+        let mut found = None;
+        self.images.foreach(|child| {
+            if let Some(hbox) = child.downcast_ref::<gtk::Box>() {
+                hbox.foreach(|child| {
+                    if let Some(eventbox) = child.downcast_ref::<gtk::EventBox>() {
+                        let area = eventbox.get_allocation();
+                        if x >= area.x && x < area.x + area.width && y >= area.y && y < area.y + area.height {
+                            found = Some(eventbox.clone());
                         }
                     }
                 });
-                unsafe { ebox.set_data("gesture", gesture); }
             }
+        });
+        found.filter(|tile| unsafe { tile.get_data::<i64>("animal") }.map(|id| *id != 0).unwrap_or(false))
+    }
+
+    /// Moves a tile to where another one is, shuffling those in between along, and keeps the
+    /// new order. The tiles are put in order by hand from then on.
+    fn move_tile(&self, tile: &EventBox, target: &EventBox) {
+        let (animal_id, target_id) = match unsafe { (tile.get_data::<i64>("animal"), target.get_data::<i64>("animal")) } {
+            (Some(animal_id), Some(target_id)) => (*animal_id, *target_id),
+            _ => return,
+        };
+        let mut order: Vec<i64> = ordered_animals().iter().filter_map(|animal| animal.rowid).collect();
+        if let (Some(from), Some(to)) = (order.iter().position(|id| *id == animal_id), order.iter().position(|id| *id == target_id)) {
+            order.remove(from);
+            order.insert(to, animal_id);
+            crate::set_setting("tile_order", TileOrder::Manual.id())
+                .and_then(|_| save_order(&order))
+                .map_err(|e| notify(Severity::Error, &format!("Error saving the order of the tiles - {}", e))).ok();
+            self.load_images();
         }
     }
     
     pub fn refresh_images(&self) -> () {
+        cache::forget_sightings();
         self.images.foreach(|child| {
             if let Some(hbox) = child.downcast_ref::<gtk::Box>() {
                 hbox.foreach(|child| {
@@ -98,30 +204,23 @@ impl gui::Gui {
     }
 }
 
-fn animal_selected(object: &EventBox) {
+fn animal_selected(gui: &gui::Gui, object: &EventBox) {
     if let Some(animal_id) = unsafe { object.get_data::<i64>("animal") } {
         if *animal_id != 0 {
             if let Some(child) = object.get_child() {
                 if let Ok(da) = child.downcast::<gtk::DrawingArea>() {
-                    crate::log_sighting(*animal_id);
+                    if let Some(rowid) = log_sighting(*animal_id, 1) {
+                        show_logged_toast(gui, *animal_id, 1, rowid);
+                    }
+                    cache::forget_today(*animal_id);
                     da.queue_draw();
                 }
             }
         } else {
-            let dialog = FileChooserDialog::with_buttons::<Window>(
-                Some("Select Bird Image File"),
-                None,
-                FileChooserAction::Open,
-                &[
-                    ("_Cancel", ResponseType::Cancel),
-                    ("_Open", ResponseType::Accept),
-                ],
-            );
-            let file_filter = FileFilter::new();
-            file_filter.add_pixbuf_formats();
-            dialog.add_filter(&file_filter);
+            let dialog = image_chooser();
             let name = Entry::new();
             name.set_placeholder_text(Some("Enter bird species name"));
+            name.set_completion(Some(&checklist_completion()));
             dialog.set_extra_widget(&name);
             loop {
                 let response = dialog.run();
@@ -129,31 +228,34 @@ fn animal_selected(object: &EventBox) {
                     if response == ResponseType::Accept {
                         let source_file =
                             dialog.get_filename().unwrap_or(PathBuf::new());
-                        if let Some(basename) = source_file.file_name() {
-                            let dest_file = gui::image_dir().join(basename);
-                            fs::copy(&source_file, &dest_file)
-                                .map_err(|e| gui::alert(&format!("Error copying file\n{}", e)[..])).ok();
+                        if let Some(basename) = copy_to_image_dir(&source_file)
+                            .map_err(|e| gui::alert(&format!("Error copying file\n{}", e)[..])).ok() {
+                            let taxon = crate::checklist::lookup(&name.get_text());
                             let animal = Animal {
                                 rowid: None,
-                                name: Some(name.get_text().to_string()),
-                                filename: Some(
-                                    basename.to_str().unwrap_or("").to_string(),
+                                name: Some(
+                                    taxon.as_ref()
+                                        .and_then(|t| t.common_name.clone())
+                                        .unwrap_or(name.get_text().to_string()),
                                 ),
-                                image: None,
+                                filename: Some(basename),
+                                image: image_blob(&source_file)
+                                    .map_err(|e| gui::alert(&format!("Error storing image in database\n{}", e)[..])).ok(),
+                                scientific_name: taxon.as_ref().and_then(|t| t.scientific_name.clone()),
+                                family: taxon.as_ref().and_then(|t| t.family.clone()),
+                                taxon_order: taxon.as_ref().and_then(|t| t.taxon_order.clone()),
+                                species_code: taxon.as_ref().and_then(|t| t.species_code.clone()),
+                                archived_at: None,
+                                position: None,
+                                color: Some(crate::colors::to_hex(crate::colors::unused())),
                             };
                             if let Some(oid) = animal
                                 .insert()
                                 .map_err(|e| gui::alert(&format!("Error adding animal to database\n{}", e)[..])).ok() {
 
-                                unsafe {
-                                    object.set_data("animal", oid);
-                                }
-                                // Update GUI
-                                if let Some(child) = object.get_child() {
-                                    if let Ok(da) = child.downcast::<gtk::DrawingArea>() {
-                                        da.show();
-                                    }
-                                }
+                                crate::mqtt::publish_discovery(oid);
+                                // The new species takes this tile, so the "add" tile moves on.
+                                gui.load_images();
                             }
                         }
                     }
@@ -166,13 +268,254 @@ fn animal_selected(object: &EventBox) {
     }
 }
 
+/// How the species tiles are ordered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TileOrder {
+    /// As they've been dragged to, with new species at the end.
+    Manual,
+    /// Most recently seen first.
+    Recent,
+    /// Most often seen over the last 30 days first.
+    Frequent,
+}
+
+impl TileOrder {
+    pub fn id(&self) -> &'static str {
+        match self {
+            TileOrder::Manual => "manual",
+            TileOrder::Recent => "recent",
+            TileOrder::Frequent => "frequent",
+        }
+    }
+
+    pub fn from_id(id: &str) -> TileOrder {
+        match id {
+            "recent" => TileOrder::Recent,
+            "frequent" => TileOrder::Frequent,
+            _ => TileOrder::Manual,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TileOrder::Manual => "In my order",
+            TileOrder::Recent => "Most recently seen",
+            TileOrder::Frequent => "Most often seen",
+        }
+    }
+
+    pub fn current() -> TileOrder {
+        TileOrder::from_id(&crate::get_setting("tile_order").unwrap_or_default())
+    }
+}
+
+/// The species shown in the grid, in the order set by the `tile_order` setting.
+fn ordered_animals() -> Vec<Animal> {
+    let animals = match TileOrder::current() {
+        TileOrder::Manual => select!(Vec<Animal> "where archived_at is null order by position is null, position, rowid"),
+        TileOrder::Recent => select!(Vec<Animal> "where archived_at is null order by (select max(seen_at) from sighting where animal_id = animal.rowid and deleted_at is null) desc, rowid"),
+        TileOrder::Frequent => select!(Vec<Animal> r#"where archived_at is null order by (select count(distinct date(seen_at, "unixepoch", "localtime")) from sighting where animal_id = animal.rowid and deleted_at is null and seen_at >= strftime("%s", "now", "-30 days")) desc, rowid"#),
+    };
+    animals
+        .map_err(|error| notify(Severity::Error, &format!("Error retrieving species - {}", error)))
+        .unwrap_or_default()
+}
+
+/// Numbers the species' positions in the order given.
+fn save_order(order: &[i64]) -> Result<(), Box<dyn std::error::Error>> {
+    execute!("BEGIN")?;
+    for (position, animal_id) in order.iter().enumerate() {
+        if let Err(error) = execute!("UPDATE animal SET position = ? WHERE rowid = ?", position as i64, animal_id) {
+            execute!("ROLLBACK").ok();
+            return Err(error.into());
+        }
+    }
+    execute!("COMMIT")?;
+    Ok(())
+}
+
+/// The grid set with `--grid`, or as many tiles as fit in `width` × `height`.
+pub fn grid_size(width: i32, height: i32) -> (usize, usize) {
+    GRID.with(|grid| *grid).unwrap_or_else(|| grid_to_fit(width, height))
+}
+
+/// As many tiles as fit in `width` × `height`.
+fn grid_to_fit(width: i32, height: i32) -> (usize, usize) {
+    if width < TILE_SIZE || height < TILE_SIZE {
+        return DEFAULT_GRID;
+    }
+    let columns = (width / TILE_SIZE).max(1) as usize;
+    let rows = (height / TILE_SIZE).max(1) as usize;
+    // One tile is always the "add" tile, so there has to be room for a species too.
+    (columns.max(if rows == 1 { 2 } else { 1 }), rows)
+}
+
+/// Parses a grid size given as `COLUMNSxROWS`, e.g. `5x3`.
+pub fn parse_grid(grid: &str) -> Result<(usize, usize), String> {
+    let error = || format!("\"{}\" is not a grid size like 4x2", grid);
+    let mut parts = grid.splitn(2, 'x');
+    let columns = parts.next().and_then(|c| c.trim().parse::<usize>().ok()).ok_or_else(error)?;
+    let rows = parts.next().and_then(|r| r.trim().parse::<usize>().ok()).ok_or_else(error)?;
+    if columns == 0 || rows == 0 || columns * rows < 2 {
+        return Err(error());
+    }
+    Ok((columns, rows))
+}
+
+/// A file chooser for picking a species' image.
+pub fn image_chooser() -> FileChooserDialog {
+    let dialog = FileChooserDialog::with_buttons::<Window>(
+        Some("Select Bird Image File"),
+        None,
+        FileChooserAction::Open,
+        &[
+            ("_Cancel", ResponseType::Cancel),
+            ("_Open", ResponseType::Accept),
+        ],
+    );
+    let file_filter = FileFilter::new();
+    file_filter.add_pixbuf_formats();
+    dialog.add_filter(&file_filter);
+    dialog
+}
+
+/// Copies an image into the image directory, returning the file name it was saved as. An image
+/// that's already there is used as it is; any other with the same name as one there is saved
+/// under a new name, e.g. `galah-2.jpg`, so it doesn't replace another species' image.
+pub fn copy_to_image_dir(source_file: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let basename = Path::new(source_file.file_name()
+        .ok_or_else(|| format!("{} is not a file", source_file.display()))?);
+    let image_dir = gui::image_dir();
+    let mut destination = image_dir.join(basename);
+    let already_there = match (fs::canonicalize(source_file), fs::canonicalize(&destination)) {
+        (Ok(source), Ok(existing)) => source == existing,
+        _ => false,
+    };
+    if !already_there {
+        let stem = basename.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = basename.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+        let mut n = 1;
+        while destination.exists() {
+            n += 1;
+            destination = image_dir.join(format!("{}-{}{}", stem, n, extension));
+        }
+        fs::copy(source_file, &destination)?;
+    }
+    Ok(destination.file_name().unwrap_or_default().to_string_lossy().into_owned())
+}
+
+/// Completes species names from the imported checklist, matching anywhere in the name.
+fn checklist_completion() -> EntryCompletion {
+    let store = ListStore::new(&[String::static_type()]);
+    for common_name in crate::checklist::common_names() {
+        store.insert_with_values(None, &[0], &[&common_name]);
+    }
+    let completion = EntryCompletion::new();
+    completion.set_model(Some(&store));
+    completion.set_text_column(0);
+    completion.set_minimum_key_length(2);
+    completion.set_match_func(|completion, key, iter| {
+        completion.get_model()
+            .and_then(|model| model.get_value(iter, 0).get::<String>().ok().flatten())
+            .map(|name| name.to_lowercase().contains(key))
+            .unwrap_or(false)
+    });
+    completion
+}
+
+fn log_sighting(animal_id: i64, individuals: i64) -> Option<i64> {
+    crate::log_sighting(animal_id, individuals)
+        .map_err(|e| notify(Severity::Error, &format!("Error logging sighting - {}", e))).ok()
+}
+
+fn animal_name(animal_id: i64) -> String {
+    select!(Animal "where rowid = ?", animal_id).ok()
+        .and_then(|animal| animal.name)
+        .unwrap_or_else(|| String::from("Bird"))
+}
+
+fn show_logged_toast(gui: &gui::Gui, animal_id: i64, individuals: i64, rowid: i64) {
+    let message = match individuals {
+        1 => format!("{} logged", animal_name(animal_id)),
+        n => format!("{} × {} logged", animal_name(animal_id), n),
+    };
+    let g = gui.clone();
+    gui.toast.show(&message, Some(std::boxed::Box::new(move || {
+        crate::undo_sighting(rowid)
+            .map_err(|e| notify(Severity::Error, &format!("Error undoing sighting - {}", e))).ok();
+        g.refresh();
+    })));
+}
+
+fn show_cleared_toast(gui: &gui::Gui, animal_id: i64, cleared: Vec<i64>) {
+    let g = gui.clone();
+    gui.toast.show(&format!("{} cleared for today", animal_name(animal_id)), Some(std::boxed::Box::new(move || {
+        crate::restore_cleared(animal_id, &cleared)
+            .map_err(|e| notify(Severity::Error, &format!("Error restoring sightings - {}", e))).ok();
+        g.refresh();
+    })));
+}
+
+fn show_tally_popover(gui: &gui::Gui, eventbox: &EventBox, x: f64, y: f64) {
+    let animal_id = match unsafe { eventbox.get_data::<i64>("animal") } {
+        Some(animal_id) if *animal_id != 0 => *animal_id,
+        _ => return,
+    };
+    let popover = Popover::new(Some(eventbox));
+    popover.set_pointing_to(&gdk::Rectangle { x: x as i32, y: y as i32, width: 1, height: 1 });
+
+    let vbox = Box::new(Orientation::Vertical, 5);
+    let individuals = SpinButton::with_range(1.0, 999.0, 1.0);
+    let log_button = Button::with_label("Log");
+    let clear_button = Button::with_label("Clear today");
+    vbox.add(&individuals);
+    vbox.add(&log_button);
+    vbox.add(&clear_button);
+    popover.add(&vbox);
+
+    let (pop, tile, g) = (popover.clone(), eventbox.clone(), gui.clone());
+    log_button.connect_clicked(move |_| {
+        let count = individuals.get_value_as_int() as i64;
+        if let Some(rowid) = log_sighting(animal_id, count) {
+            show_logged_toast(&g, animal_id, count, rowid);
+        }
+        cache::forget_today(animal_id);
+        if let Some(da) = tile.get_child() {
+            da.queue_draw();
+        }
+        g.chart.sighting_changed(animal_id);
+        pop.hide();
+    });
+    let (pop, tile, g) = (popover.clone(), eventbox.clone(), gui.clone());
+    clear_button.connect_clicked(move |_| {
+        if let Some(cleared) = crate::clear_sighting(animal_id)
+            .map_err(|e| notify(Severity::Error, &format!("Error clearing sightings - {}", e))).ok() {
+            show_cleared_toast(&g, animal_id, cleared);
+        }
+        cache::forget_today(animal_id);
+        if let Some(da) = tile.get_child() {
+            da.queue_draw();
+        }
+        g.chart.sighting_changed(animal_id);
+        pop.hide();
+    });
+
+    popover.show_all();
+}
+
 fn draw_image(da: &gtk::DrawingArea, context: &cairo::Context) -> gtk::Inhibit {
     if let Some(eventbox) = da.get_parent() {
         if let Some(animal_id) = unsafe { eventbox.get_data::<i64>("animal") } {
+            // Images are scaled for the screen's pixels, which HiDPI screens have more of.
+            let scale = da.get_scale_factor();
             let a_width = da.get_allocated_width();
             let a_height = da.get_allocated_height();
             let diff = a_width - a_height;
-            let pb = get_animal_pixbuf(animal_id, a_width, a_height);
+            let seen_today = today_individuals(*animal_id);
+            let pb = match get_animal_pixbuf(animal_id, a_width * scale, a_height * scale, scale, seen_today > 0) {
+                Some(pb) => pb,
+                None => return Inhibit(false),
+            };
             let mut x_offset = 0.0;
             let mut y_offset = 0.0;
             if diff < 0 {
@@ -180,45 +523,163 @@ fn draw_image(da: &gtk::DrawingArea, context: &cairo::Context) -> gtk::Inhibit {
             } else {
                 x_offset = diff as f64 / 2.0;
             }
-            context.set_source_pixbuf(&pb, x_offset, y_offset);
+            context.save();
+            context.scale(1.0 / scale as f64, 1.0 / scale as f64);
+            context.set_source_pixbuf(&pb, x_offset * scale as f64, y_offset * scale as f64);
             context.paint();
+            context.restore();
+            if seen_today > 1 {
+                draw_tally(context, seen_today, x_offset + 10.0, y_offset + 10.0);
+            }
         }
     }
     Inhibit(false)
 }
 
-fn get_animal_pixbuf(animal_id: &i64, width: i32, height: i32) -> gdk_pixbuf::Pixbuf {
-    let mut pb = gdk_pixbuf::Pixbuf::from_file_at_scale(gui::image_dir().join(Path::new("unknown.png")), width, height, true)
-                .map_err(|e| gui::alert(&format!("Couldn't load pixbuf from file\n{}", e)[..])).ok()
-                .unwrap_or(gdk_pixbuf::Pixbuf::new(gdk_pixbuf::Colorspace::Rgb, true, 8, 180, 180).unwrap());
+fn draw_tally(context: &cairo::Context, seen_today: i64, x: f64, y: f64) {
+    let text = seen_today.to_string();
+    context.select_font_face("Sans", cairo::FontSlant::Normal, cairo::FontWeight::Bold);
+    context.set_font_size(24.0);
+    let extents = context.text_extents(&text);
+    context.set_source_rgba(0.0, 0.0, 0.0, 0.6);
+    context.rectangle(x, y, extents.x_advance + 12.0, extents.height + 12.0);
+    context.fill();
+    context.set_source_rgb(1.0, 1.0, 1.0);
+    context.move_to(x + 6.0, y + 6.0 - extents.y_bearing);
+    context.show_text(&text);
+}
 
-    if let Ok(animal) = select!(Animal "where rowid = ?", animal_id) {
-        let res = match &animal.filename {
-            Some(filename) => gdk_pixbuf::Pixbuf::from_file_at_scale(gui::image_dir().join(Path::new(filename)), width, height, true),
-            None => gdk_pixbuf::Pixbuf::from_file_at_scale(gui::image_dir().join(Path::new("unknown.png")), width, height, true)
-        };
-        pb = res
-            .map_err(|e| gui::alert(&format!("Couldn't load pixbuf from file\n{}", e)[..])).ok()
-            .unwrap_or(gdk_pixbuf::Pixbuf::new(gdk_pixbuf::Colorspace::Rgb, true, 8, 180, 180).unwrap());
-        if let Ok(today_count) = select!(i64 "count(*) from sighting where animal_id = ? and date(seen_at, \"unixepoch\", \"localtime\") = date(\"now\", \"localtime\")",
-                animal_id) {
-            if today_count > 0 {
-                add_tick(&pb);
+fn today_individuals(animal_id: i64) -> i64 {
+    if animal_id == 0 {
+        return 0;
+    }
+    cache::today(animal_id, || {
+        let site_id = crate::active_site();
+        let observer_id = crate::active_observer();
+        select!(i64 "coalesce(sum(coalesce(individuals, 1)), 0) from sighting where deleted_at is null and animal_id = ? and (? is null or site_id = ?) and (? is null or observer_id = ?) and date(seen_at, \"unixepoch\", \"localtime\") = date(\"now\", \"localtime\")",
+                animal_id, site_id, site_id, observer_id, observer_id).unwrap_or(0)
+    })
+}
+
+/// A species' image scaled to fit `width` × `height`, with a tick on it if it's been seen today.
+/// A species' image at `width` by `height` device pixels, on a screen with `scale` device pixels
+/// to each logical one. `None` only if not even a blank image could be made.
+pub fn get_animal_pixbuf(animal_id: &i64, width: i32, height: i32, scale: i32, seen_today: bool) -> Option<gdk_pixbuf::Pixbuf> {
+    cache::pixbuf(*animal_id, width, height, scale, seen_today, || {
+        let pb = load_animal_pixbuf(*animal_id, width, height)?;
+        if seen_today {
+            add_tick(&pb, scale);
+        }
+        Some(pb)
+    })
+}
+
+fn load_animal_pixbuf(animal_id: i64, width: i32, height: i32) -> Option<gdk_pixbuf::Pixbuf> {
+    let load_file = |filename: &str| {
+        gdk_pixbuf::Pixbuf::from_file_at_scale(gui::image_dir().join(Path::new(filename)), width, height, true)
+    };
+    let res = match select!(Animal "where rowid = ?", animal_id) {
+        Ok(animal) => match animal.image.as_ref().and_then(|image| pixbuf_from_blob(image, width, height).ok()) {
+            Some(pixbuf) => Ok(pixbuf),
+            None => load_file(animal.filename.as_deref().unwrap_or("unknown.png")),
+        },
+        Err(_) => load_file(if animal_id == 0 { "add_new.png" } else { "unknown.png" }),
+    };
+    res.or_else(|e| {
+            notify(Severity::Warning, &format!("Couldn't load the image for a bird - {}", e));
+            load_file("unknown.png")
+        })
+        .ok()
+        .or_else(|| {
+            let blank = gdk_pixbuf::Pixbuf::new(gdk_pixbuf::Colorspace::Rgb, true, 8, width.max(1), height.max(1))?;
+            blank.fill(0);
+            Some(blank)
+        })
+}
+
+/// Encodes an image file for storing in `Animal.image`, shrinking it to `STORED_IMAGE_SIZE` if it is larger.
+pub fn image_blob(path: &Path) -> Result<Blob, glib::Error> {
+    let mut pb = gdk_pixbuf::Pixbuf::from_file(path)?;
+    let (width, height) = (pb.get_width(), pb.get_height());
+    let longest = width.max(height);
+    if longest > STORED_IMAGE_SIZE {
+        let scale = STORED_IMAGE_SIZE as f64 / longest as f64;
+        let scaled_width = ((width as f64 * scale).round() as i32).max(1);
+        let scaled_height = ((height as f64 * scale).round() as i32).max(1);
+        if let Some(scaled) = pb.scale_simple(scaled_width, scaled_height, gdk_pixbuf::InterpType::Bilinear) {
+            pb = scaled;
+        }
+    }
+    if pb.get_has_alpha() {
+        pb.save_to_bufferv("png", &[])
+    } else {
+        pb.save_to_bufferv("jpeg", &[("quality", "90")])
+    }
+}
+
+fn pixbuf_from_blob(image: &Blob, width: i32, height: i32) -> Result<gdk_pixbuf::Pixbuf, glib::Error> {
+    let stream = gio::MemoryInputStream::from_bytes(&glib::Bytes::from(&image[..]));
+    gdk_pixbuf::Pixbuf::from_stream_at_scale(&stream, width, height, true, gio::NONE_CANCELLABLE)
+}
+
+/// Copies the image file of any species that doesn't yet have one stored into the database.
+/// Species added before images were stored this way are picked up the first time this runs;
+/// once every image has been stored it is recorded in the `images_stored` setting and skipped.
+pub fn store_missing_images() {
+    if crate::get_setting("images_stored").is_some() {
+        return;
+    }
+    let animals = match select!(Vec<Animal> "where image is null and filename is not null") {
+        Ok(animals) => animals,
+        Err(error) => {
+            notify(Severity::Error, &format!("Error finding species without stored images - {}", error));
+            return;
+        }
+    };
+    let mut stored_all = true;
+    for animal in animals {
+        if let (Some(rowid), Some(filename)) = (animal.rowid, animal.filename) {
+            match image_blob(&gui::image_dir().join(Path::new(&filename))) {
+                Ok(image) => {
+                    if let Err(error) = execute!("UPDATE animal SET image = ? WHERE rowid = ?", image, rowid) {
+                        notify(Severity::Error, &format!("Error storing image {} - {}", filename, error));
+                        stored_all = false;
+                    }
+                }
+                Err(error) => {
+                    notify(Severity::Warning, &format!("Error loading image {} - {}", filename, error));
+                    stored_all = false;
+                }
             }
         }
     }
-    pb
+    if stored_all {
+        crate::set_setting("images_stored", "1")
+            .map_err(|error| notify(Severity::Error, &format!("Error recording that images are stored - {}", error))).ok();
+    }
 }
 
-fn add_tick(dest_pb: &gdk_pixbuf::Pixbuf) {
-    if let Some(tick_pb) = gdk_pixbuf::Pixbuf::from_file(&gui::image_dir().join(Path::new("tick.png")))
-            .map_err(|e| gui::alert(&format!("Couldn't load pixbuf from file\n{}", e)[..])).ok() {
+thread_local! {
+    /// The grid size fixed with `--grid`, which is only set before the window opens.
+    static GRID: Option<(usize, usize)> = crate::get_setting("grid").filter(|grid| grid != "")
+        .map(|grid| parse_grid(&grid).unwrap_or(DEFAULT_GRID));
+    static TICK: Option<gdk_pixbuf::Pixbuf> = gdk_pixbuf::Pixbuf::from_file(&gui::image_dir().join(Path::new("tick.png")))
+        .map_err(|error| notify(Severity::Warning, &format!("Error loading tick.png - {}", error))).ok();
+}
+
+/// Puts the tick in the bottom right corner, sized for a screen with `scale` device pixels to
+/// each logical one.
+fn add_tick(dest_pb: &gdk_pixbuf::Pixbuf, scale: i32) {
+    if let Some(tick_pb) = TICK.with(|tick| tick.clone()) {
         let dw = dest_pb.get_width();
         let dh = dest_pb.get_height();
-        let tw = tick_pb.get_width();
-        let th = tick_pb.get_height();
-        let ow = dw - tw - 10;
-        let oh = dh - th - 10;
+        let tw = tick_pb.get_width() * scale;
+        let th = tick_pb.get_height() * scale;
+        let ow = dw - tw - 10 * scale;
+        let oh = dh - th - 10 * scale;
+        if ow < 0 || oh < 0 {
+            return;
+        }
         tick_pb.composite(
             &dest_pb,
             ow,
@@ -227,10 +688,41 @@ fn add_tick(dest_pb: &gdk_pixbuf::Pixbuf) {
             th,
             ow as f64,
             oh as f64,
-            1.0,
-            1.0,
+            scale as f64,
+            scale as f64,
             gdk_pixbuf::InterpType::Bilinear,
             255,
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_grid_reads_columns_and_rows() {
+        assert_eq!(parse_grid("5x3"), Ok((5, 3)));
+        assert_eq!(parse_grid(" 2 x 1 "), Ok((2, 1)));
+        assert_eq!(parse_grid("1x2"), Ok((1, 2)));
+    }
+
+    #[test]
+    fn parse_grid_needs_room_for_a_species_and_the_add_tile() {
+        for grid in &["0x2", "2x0", "1x1", "5", "5x", "x3", "ax3", "5x3x2", "-1x3"] {
+            assert!(parse_grid(grid).is_err(), "{} was accepted", grid);
+        }
+    }
+
+    #[test]
+    fn grid_to_fit_fills_the_window() {
+        assert_eq!(grid_to_fit(TILE_SIZE * 5, TILE_SIZE * 3), (5, 3));
+        assert_eq!(grid_to_fit(TILE_SIZE * 5 + TILE_SIZE / 2, TILE_SIZE * 3 - 1), (5, 2));
+        // A single row still has room for a species beside the "add" tile.
+        assert_eq!(grid_to_fit(TILE_SIZE, TILE_SIZE), (2, 1));
+        assert_eq!(grid_to_fit(TILE_SIZE, TILE_SIZE * 2), (1, 2));
+        // Before the window has a size.
+        assert_eq!(grid_to_fit(1, 1), DEFAULT_GRID);
+        assert_eq!(grid_to_fit(TILE_SIZE * 4, 0), DEFAULT_GRID);
+    }
+}
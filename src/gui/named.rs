@@ -0,0 +1,125 @@
+extern crate gtk;
+
+use crate::gui;
+use gtk::prelude::*;
+use gtk::ComboBoxText;
+use std::error::Error;
+
+const NEW_ID: &str = "new";
+const NONE_ID: &str = "none";
+
+/// Something sightings are tagged with and picked by name, like a site or an observer. The one
+/// picked is kept in a setting, and new sightings are logged against it.
+pub struct Named {
+    /// What one is called, e.g. "site", and the same with a capital for titles.
+    pub noun: &'static str,
+    pub title: &'static str,
+    /// The picker's choice for logging against none of them, e.g. "No site".
+    pub none: &'static str,
+    /// The setting holding the rowid of the one picked.
+    pub setting: &'static str,
+    /// The chart filter for these, and the choices it has before the named ones (the first
+    /// being the one to fall back to).
+    pub filter: fn(&gui::Gui) -> &ComboBoxText,
+    pub filter_choices: &'static [(&'static str, &'static str)],
+    /// Every one there is, as rowid and name, in the order they're listed.
+    pub all: fn() -> Vec<(i64, String)>,
+    pub insert: fn(String) -> Result<i64, Box<dyn Error>>,
+}
+
+impl gui::Gui {
+
+    /// Builds the combo used to pick which one sightings are logged against.
+    pub fn named_picker(&self, named: &'static Named) -> ComboBoxText {
+        let picker = ComboBoxText::new();
+        populate_picker(named, &picker);
+
+        let gui = self.clone();
+        picker.connect_changed(move |combo| {
+            match combo.get_active_id() {
+                Some(id) if id == NEW_ID => {
+                    if let Some(rowid) = add(named) {
+                        set_active(named, rowid);
+                    }
+                    populate_picker(named, combo);
+                    gui.populate_filter(named);
+                }
+                Some(id) if id == NONE_ID => {
+                    if active(named).is_some() {
+                        clear_active(named);
+                        gui.refresh();
+                    }
+                }
+                Some(id) => {
+                    if let Ok(rowid) = id.parse::<i64>() {
+                        if active(named) != Some(rowid) {
+                            set_active(named, rowid);
+                            gui.refresh();
+                        }
+                    }
+                }
+                None => (),
+            }
+        });
+
+        picker
+    }
+
+    /// Fills the chart's filter with the current list, keeping the choice made if it's still there.
+    pub fn populate_filter(&self, named: &Named) {
+        let combo = (named.filter)(self);
+        let active = combo.get_active_id();
+        let populating = self.populating.replace(true);
+        combo.remove_all();
+        for &(id, label) in named.filter_choices {
+            combo.append(Some(id), label);
+        }
+        for (rowid, name) in (named.all)() {
+            combo.append(Some(&rowid.to_string()), &name);
+        }
+        if active.is_none() || !combo.set_active_id(active.as_deref()) {
+            combo.set_active_id(named.filter_choices.first().map(|&(id, _)| id));
+        }
+        self.populating.set(populating);
+        // Only tell the chart if the one it was showing has gone.
+        if combo.get_active_id() != active {
+            combo.emit("changed", &[]).ok();
+        }
+    }
+}
+
+fn active(named: &Named) -> Option<i64> {
+    crate::get_setting(named.setting).and_then(|value| value.parse::<i64>().ok())
+}
+
+fn set_active(named: &Named, rowid: i64) {
+    if let Err(error) = crate::set_setting(named.setting, &rowid.to_string()) {
+        gui::alert(&format!("Error saving active {} - {}", named.noun, error));
+    }
+}
+
+fn clear_active(named: &Named) {
+    if let Err(error) = crate::delete_setting(named.setting) {
+        gui::alert(&format!("Error clearing active {} - {}", named.noun, error));
+    }
+}
+
+fn populate_picker(named: &Named, picker: &ComboBoxText) {
+    picker.remove_all();
+    picker.append(Some(NONE_ID), named.none);
+    for (rowid, name) in (named.all)() {
+        picker.append(Some(&rowid.to_string()), &name);
+    }
+    picker.append(Some(NEW_ID), &format!("New {}…", named.noun));
+    // The one picked may have gone, in which case none is.
+    let active = active(named).map(|rowid| rowid.to_string());
+    if !picker.set_active_id(Some(active.as_deref().unwrap_or(NONE_ID))) {
+        picker.set_active_id(Some(NONE_ID));
+    }
+}
+
+fn add(named: &Named) -> Option<i64> {
+    let name = gui::prompt(&format!("New {}", named.title), &format!("Enter {} name", named.noun))?;
+    (named.insert)(name)
+        .map_err(|e| gui::alert(&format!("Error adding {} to database\n{}", named.noun, e)[..])).ok()
+}
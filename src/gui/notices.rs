@@ -0,0 +1,157 @@
+extern crate gtk;
+
+use gtk::prelude::*;
+use gtk::{InfoBar, Label};
+use std::cell::{Cell, RefCell};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// Information and warnings go away by themselves after this long; errors stay until closed.
+const NOTICE_SECONDS: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn label(&self) -> &'static str {
+        match self {
+            Severity::Info => "INFO",
+            Severity::Warning => "WARNING",
+            Severity::Error => "ERROR",
+        }
+    }
+
+    fn message_type(&self) -> gtk::MessageType {
+        match self {
+            Severity::Info => gtk::MessageType::Info,
+            Severity::Warning => gtk::MessageType::Warning,
+            Severity::Error => gtk::MessageType::Error,
+        }
+    }
+}
+
+/// A bar across the top of the window for problems that shouldn't stop everything, like an
+/// image that won't load. The same message repeated is counted rather than shown again.
+#[derive(Clone)]
+pub struct Notices {
+    pub bar: InfoBar,
+    label: Label,
+    current: Rc<RefCell<Option<(Severity, String)>>>,
+    repeats: Rc<Cell<u32>>,
+    generation: Rc<Cell<u32>>,
+}
+
+thread_local! {
+    static NOTICES: RefCell<Option<Notices>> = RefCell::new(None);
+}
+
+impl Notices {
+    pub fn new() -> Notices {
+        let n = Notices {
+            bar: InfoBar::new(),
+            label: Label::new(None),
+            current: Rc::new(RefCell::new(None)),
+            repeats: Rc::new(Cell::new(0)),
+            generation: Rc::new(Cell::new(0)),
+        };
+
+        n.label.set_line_wrap(true);
+        n.label.set_xalign(0.0);
+        n.bar.get_content_area().add(&n.label);
+        n.bar.set_show_close_button(true);
+        n.bar.set_valign(gtk::Align::Start);
+        n.bar.set_no_show_all(true);
+        let notices = n.clone();
+        n.bar.connect_response(move |_, _| notices.hide());
+
+        // Notices raised anywhere in the GUI go to this bar.
+        NOTICES.with(|notices| *notices.borrow_mut() = Some(n.clone()));
+
+        n
+    }
+
+    /// Shows a message, returning whether it was a repeat of the one already showing.
+    fn show(&self, severity: Severity, message: &str) -> bool {
+        let repeat = self.bar.get_visible()
+            && self.current.borrow().as_ref().map(|(s, m)| *s == severity && m == message).unwrap_or(false);
+        if repeat {
+            self.repeats.set(self.repeats.get() + 1);
+            self.label.set_text(&format!("{} (×{})", message, self.repeats.get()));
+        } else {
+            // Don't cover up an error with something less important.
+            let showing = self.current.borrow().as_ref().map(|(s, _)| *s).filter(|_| self.bar.get_visible());
+            if showing.map(|showing| showing > severity).unwrap_or(false) {
+                return false;
+            }
+            *self.current.borrow_mut() = Some((severity, message.to_string()));
+            self.repeats.set(1);
+            self.label.set_text(message);
+            self.bar.set_message_type(severity.message_type());
+        }
+        self.label.show();
+        self.bar.show();
+
+        let generation = self.generation.get().wrapping_add(1);
+        self.generation.set(generation);
+        if severity < Severity::Error {
+            let notices = self.clone();
+            glib::timeout_add_seconds_local(NOTICE_SECONDS, move || {
+                if notices.generation.get() == generation {
+                    notices.hide();
+                }
+                glib::Continue(false)
+            });
+        }
+        repeat
+    }
+
+    fn hide(&self) {
+        self.bar.hide();
+        self.current.borrow_mut().take();
+    }
+}
+
+/// Shows a message without blocking, and writes it to the log file. Before the window is
+/// built it's only logged (and printed).
+pub fn notify(severity: Severity, message: &str) {
+    let repeat = NOTICES.with(|notices| match notices.borrow().as_ref() {
+        Some(notices) => notices.show(severity, message),
+        None => {
+            println!("{}", message);
+            false
+        }
+    });
+    if !repeat {
+        log(severity, message);
+    }
+}
+
+/// Where notices are logged, alongside the images.
+pub fn log_file() -> PathBuf {
+    super::data_dir().join("bird_counter.log")
+}
+
+/// Appends a timestamped line to the log file.
+pub fn log(severity: Severity, message: &str) {
+    let path = log_file();
+    let result = path.parent().map(fs::create_dir_all).unwrap_or(Ok(()))
+        .and_then(|_| OpenOptions::new().create(true).append(true).open(&path))
+        .and_then(|mut file| {
+            writeln!(
+                file,
+                "{} {} {}",
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+                severity.label(),
+                message.replace('\n', " "),
+            )
+        });
+    if let Err(error) = result {
+        println!("Error writing to {}.\nError: {}", path.display(), error);
+    }
+}
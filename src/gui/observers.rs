@@ -0,0 +1,39 @@
+extern crate gtk;
+
+use crate::Observer;
+use crate::gui;
+use gtk::ComboBoxText;
+use std::error::Error;
+use turbosql::select;
+
+/// Who's logging sightings, switched above the tiles and filtered by on the chart.
+pub static OBSERVERS: gui::named::Named = gui::named::Named {
+    noun: "observer",
+    title: "Observer",
+    none: "Nobody",
+    setting: "active_observer",
+    filter,
+    filter_choices: &[("all", "Everyone")],
+    all,
+    insert,
+};
+
+fn filter(gui: &gui::Gui) -> &ComboBoxText {
+    &gui.observer_filter
+}
+
+fn all() -> Vec<(i64, String)> {
+    observers().into_iter().filter_map(|observer| Some((observer.rowid?, observer.name?))).collect()
+}
+
+fn insert(name: String) -> Result<i64, Box<dyn Error>> {
+    Ok(Observer { rowid: None, name: Some(name) }.insert()?)
+}
+
+pub fn observers() -> Vec<Observer> {
+    select!(Vec<Observer> "order by name").unwrap_or_default()
+}
+
+pub fn observer_filter_from_id(id: &str) -> Option<i64> {
+    id.parse::<i64>().ok()
+}
@@ -0,0 +1,46 @@
+extern crate gtk;
+
+use crate::Site;
+use crate::gui;
+use crate::charts;
+use gtk::ComboBoxText;
+use std::error::Error;
+use turbosql::select;
+
+/// Where sightings are logged, picked above the tiles and filtered or split by on the chart.
+pub static SITES: gui::named::Named = gui::named::Named {
+    noun: "site",
+    title: "Site",
+    none: "No site",
+    setting: "active_site",
+    filter,
+    filter_choices: &[("all", "All sites"), ("split", "Split by site")],
+    all,
+    insert,
+};
+
+fn filter(gui: &gui::Gui) -> &ComboBoxText {
+    &gui.site_filter
+}
+
+fn all() -> Vec<(i64, String)> {
+    sites().into_iter().filter_map(|site| Some((site.rowid?, site.name?))).collect()
+}
+
+fn insert(name: String) -> Result<i64, Box<dyn Error>> {
+    Ok(Site { rowid: None, name: Some(name) }.insert()?)
+}
+
+pub fn sites() -> Vec<Site> {
+    select!(Vec<Site> "order by name").unwrap_or_default()
+}
+
+pub fn site_filter_from_id(id: &str) -> charts::SiteFilter {
+    match id {
+        "split" => charts::SiteFilter::Split,
+        _ => match id.parse::<i64>() {
+            Ok(site_id) => charts::SiteFilter::Only(site_id),
+            Err(_) => charts::SiteFilter::All,
+        },
+    }
+}
@@ -0,0 +1,250 @@
+extern crate gtk;
+
+use crate::colors;
+use crate::gui;
+use crate::gui::images::TileOrder;
+use crate::Animal;
+use gtk::prelude::*;
+use gtk::{Button, ColorButton, Image, Label, ListBox, MessageDialog, Orientation, ResponseType, ScrolledWindow, Window};
+use std::error::Error;
+use std::path::PathBuf;
+use turbosql::{execute, select};
+
+const THUMBNAIL_SIZE: i32 = 64;
+
+fn rename(animal_id: i64, name: &str) -> Result<(), Box<dyn Error>> {
+    execute!("UPDATE animal SET name = ? WHERE rowid = ?", name, animal_id)?;
+    crate::mqtt::publish_discovery(animal_id);
+    Ok(())
+}
+
+fn set_image(animal_id: i64, source_file: &std::path::Path) -> Result<(), Box<dyn Error>> {
+    let image = gui::images::image_blob(source_file)?;
+    let filename = gui::images::copy_to_image_dir(source_file)?;
+    execute!("UPDATE animal SET image = ?, filename = ? WHERE rowid = ?", image, filename, animal_id)?;
+    gui::cache::forget_image(animal_id);
+    Ok(())
+}
+
+fn archive(animal_id: i64) -> Result<(), Box<dyn Error>> {
+    execute!("UPDATE animal SET archived_at = ? WHERE rowid = ?", chrono::Local::now().timestamp(), animal_id)?;
+    Ok(())
+}
+
+fn unarchive(animal_id: i64) -> Result<(), Box<dyn Error>> {
+    execute!("UPDATE animal SET archived_at = null WHERE rowid = ?", animal_id)?;
+    Ok(())
+}
+
+/// Deletes a species along with all of its sightings, including those in the trash.
+fn delete(animal_id: i64) -> Result<(), Box<dyn Error>> {
+    execute!("BEGIN")?;
+    let result = execute!("DELETE FROM sighting WHERE animal_id = ?", animal_id)
+        .and_then(|_| execute!("DELETE FROM animal WHERE rowid = ?", animal_id));
+    match result {
+        Ok(_) => execute!("COMMIT")?,
+        Err(error) => {
+            execute!("ROLLBACK").ok();
+            return Err(error.into());
+        }
+    };
+    gui::cache::forget_image(animal_id);
+    crate::mqtt::remove_discovery(animal_id);
+    Ok(())
+}
+
+fn sighting_count(animal_id: i64) -> i64 {
+    select!(i64 "count(*) from sighting where animal_id = ?", animal_id).unwrap_or(0)
+}
+
+fn confirm(message: &str) -> bool {
+    let dialog = MessageDialog::new::<Window>(
+        None,
+        gtk::DialogFlags::DESTROY_WITH_PARENT,
+        gtk::MessageType::Warning,
+        gtk::ButtonsType::OkCancel,
+        message,
+    );
+    let response = dialog.run();
+    dialog.hide();
+    response == ResponseType::Ok
+}
+
+impl gui::Gui {
+
+    /// Builds the page for renaming, re-imaging, archiving and deleting species.
+    pub fn build_species(&self) -> gtk::Box {
+        let page = gtk::Box::new(Orientation::Vertical, 5);
+
+        let header = gtk::Box::new(Orientation::Horizontal, 5);
+        let back = Button::with_label("Back");
+        let stack = self.stack.clone();
+        back.connect_clicked(move |_| {
+            stack.set_visible_child_full("birds", gtk::StackTransitionType::SlideDown);
+        });
+        header.pack_start(&back, false, false, 0);
+        header.pack_start(&Label::new(Some("Species")), true, true, 0);
+        header.pack_end(&self.tile_order_picker(), false, false, 0);
+        header.pack_end(&Label::new(Some("Tiles:")), false, false, 0);
+        page.pack_start(&header, false, false, 0);
+
+        let scroller = ScrolledWindow::new(gtk::NONE_ADJUSTMENT, gtk::NONE_ADJUSTMENT);
+        scroller.set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+        scroller.add(&self.species);
+        page.pack_start(&scroller, true, true, 0);
+
+        page
+    }
+
+    /// Picks whether tiles stay where they've been dragged to or follow what's been seen.
+    fn tile_order_picker(&self) -> gtk::ComboBoxText {
+        let picker = gtk::ComboBoxText::new();
+        for order in &[TileOrder::Manual, TileOrder::Recent, TileOrder::Frequent] {
+            picker.append(Some(order.id()), order.label());
+        }
+        picker.set_active_id(Some(TileOrder::current().id()));
+        let gui = self.clone();
+        picker.connect_changed(move |combo| {
+            if let Some(id) = combo.get_active_id() {
+                crate::set_setting("tile_order", TileOrder::from_id(&id).id())
+                    .map_err(|e| gui::alert(&format!("Error saving the order of the tiles - {}", e))).ok();
+                gui.load_images();
+            }
+        });
+        picker
+    }
+
+    /// Fills the picker for which species the calendar chart is for, keeping the current choice.
+    pub fn populate_chart_species(&self) {
+        let combo = &self.chart_species;
+        let active = combo.get_active_id();
+        let populating = self.populating.replace(true);
+        combo.remove_all();
+        // The calendar starts on the first of these until another is picked.
+        let animals = select!(Vec<Animal> "where archived_at is null and rowid in (select animal_id from sighting where deleted_at is null) order by name").unwrap_or_default();
+        for animal in animals {
+            if let (Some(rowid), Some(name)) = (animal.rowid, animal.name) {
+                combo.append(Some(&rowid.to_string()), &name);
+            }
+        }
+        if active.is_none() || !combo.set_active_id(active.as_deref()) {
+            combo.set_active(Some(0));
+        }
+        self.populating.set(populating);
+        // Only tell the chart if the species it was showing has gone.
+        if combo.get_active_id() != active {
+            combo.emit("changed", &[]).ok();
+        }
+    }
+
+    pub fn show_species(&self) {
+        self.populate_species();
+        self.stack.set_visible_child_full("species", gtk::StackTransitionType::SlideUp);
+    }
+
+    /// Redraws everything that shows species after one has been changed.
+    fn species_changed(&self) {
+        self.populate_species();
+        self.load_images();
+        self.chart.invalidate();
+        self.refresh();
+    }
+
+    fn populate_species(&self) {
+        let list: &ListBox = &self.species;
+        list.foreach(|row| list.remove(row));
+
+        let animals = select!(Vec<Animal> "order by archived_at is not null, name").unwrap_or_default();
+        if animals.is_empty() {
+            list.add(&Label::new(Some("No species have been added yet.")));
+        }
+        for animal in animals {
+            let animal_id = match animal.rowid {
+                Some(rowid) => rowid,
+                None => continue,
+            };
+            let name = animal.name.clone().unwrap_or_default();
+            let archived = animal.archived_at.is_some();
+            let row = gtk::Box::new(Orientation::Horizontal, 10);
+
+            let pixbuf = gui::images::get_animal_pixbuf(&animal_id, THUMBNAIL_SIZE, THUMBNAIL_SIZE, 1, false);
+            row.pack_start(&Image::from_pixbuf(pixbuf.as_ref()), false, false, 0);
+            let text = if archived { format!("{} (archived)", name) } else { name.clone() };
+            let label = Label::new(Some(&text));
+            label.set_xalign(0.0);
+            label.set_sensitive(!archived);
+            row.pack_start(&label, true, true, 0);
+
+            let delete_button = Button::with_label("Delete");
+            let (gui, n) = (self.clone(), name.clone());
+            delete_button.connect_clicked(move |_| {
+                let message = match sighting_count(animal_id) {
+                    0 => format!("Delete {}?", n),
+                    1 => format!("Delete {} and its 1 sighting?\nArchive it instead to keep the sighting.", n),
+                    count => format!("Delete {} and its {} sightings?\nArchive it instead to keep the sightings.", n, count),
+                };
+                if confirm(&message) {
+                    delete(animal_id).map_err(|e| gui::alert(&format!("Error deleting {} - {}", n, e))).ok();
+                    gui.species_changed();
+                }
+            });
+            row.pack_end(&delete_button, false, false, 0);
+
+            let archive_button = Button::with_label(if archived { "Restore" } else { "Archive" });
+            let (gui, n) = (self.clone(), name.clone());
+            archive_button.connect_clicked(move |_| {
+                let result = if archived { unarchive(animal_id) } else { archive(animal_id) };
+                result.map_err(|e| gui::alert(&format!("Error archiving {} - {}", n, e))).ok();
+                gui.species_changed();
+            });
+            row.pack_end(&archive_button, false, false, 0);
+
+            let image_button = Button::with_label("Image…");
+            let (gui, n) = (self.clone(), name.clone());
+            image_button.connect_clicked(move |_| {
+                let dialog = gui::images::image_chooser();
+                if dialog.run() == ResponseType::Accept {
+                    let source_file = dialog.get_filename().unwrap_or(PathBuf::new());
+                    set_image(animal_id, &source_file)
+                        .map_err(|e| gui::alert(&format!("Error changing the image of {} - {}", n, e))).ok();
+                }
+                dialog.hide();
+                gui.species_changed();
+            });
+            row.pack_end(&image_button, false, false, 0);
+
+            let (r, g, b) = colors::of(&animal);
+            let color_button = ColorButton::with_rgba(&gdk::RGBA {
+                red: r as f64 / 255.0,
+                green: g as f64 / 255.0,
+                blue: b as f64 / 255.0,
+                alpha: 1.0,
+            });
+            color_button.set_use_alpha(false);
+            color_button.set_title(&format!("Colour of {} in charts", name));
+            let (gui, n) = (self.clone(), name.clone());
+            color_button.connect_color_set(move |button| {
+                let rgba = button.get_rgba();
+                let byte = |c: f64| (c * 255.0).round() as u8;
+                colors::set(animal_id, (byte(rgba.red), byte(rgba.green), byte(rgba.blue)))
+                    .map_err(|e| gui::alert(&format!("Error changing the colour of {} - {}", n, e))).ok();
+                gui.chart.update();
+            });
+            row.pack_end(&color_button, false, false, 0);
+
+            let rename_button = Button::with_label("Rename");
+            let gui = self.clone();
+            rename_button.connect_clicked(move |_| {
+                if let Some(new_name) = gui::prompt(&format!("Rename {}", name), &name) {
+                    rename(animal_id, &new_name)
+                        .map_err(|e| gui::alert(&format!("Error renaming {} - {}", name, e))).ok();
+                    gui.species_changed();
+                }
+            });
+            row.pack_end(&rename_button, false, false, 0);
+
+            list.add(&row);
+        }
+        list.show_all();
+    }
+}
@@ -0,0 +1,71 @@
+extern crate gtk;
+
+use gtk::prelude::*;
+use gtk::{Box, Button, Label, Orientation, Revealer};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+const TOAST_SECONDS: u32 = 6;
+
+/// A short-lived message shown over the bottom of the window, with an optional undo button.
+#[derive(Clone)]
+pub struct Toast {
+    pub revealer: Revealer,
+    label: Label,
+    undo: Button,
+    action: Rc<RefCell<Option<std::boxed::Box<dyn Fn()>>>>,
+    generation: Rc<Cell<u32>>,
+}
+
+impl Toast {
+    pub fn new() -> Toast {
+        let t = Toast {
+            revealer: Revealer::new(),
+            label: Label::new(None),
+            undo: Button::with_label("Undo"),
+            action: Rc::new(RefCell::new(None)),
+            generation: Rc::new(Cell::new(0)),
+        };
+
+        let hbox = Box::new(Orientation::Horizontal, 10);
+        hbox.get_style_context().add_class("app-notification");
+        hbox.pack_start(&t.label, true, true, 0);
+        hbox.pack_end(&t.undo, false, false, 0);
+        t.revealer.add(&hbox);
+        t.revealer.set_halign(gtk::Align::Center);
+        t.revealer.set_valign(gtk::Align::End);
+        t.revealer.set_transition_type(gtk::RevealerTransitionType::SlideUp);
+
+        let toast = t.clone();
+        t.undo.connect_clicked(move |_| {
+            if let Some(action) = toast.action.borrow_mut().take() {
+                action();
+            }
+            toast.revealer.set_reveal_child(false);
+        });
+
+        t
+    }
+
+    /// Shows `message`, replacing any toast already showing. The undo button is only shown
+    /// when an `undo` action is given, and it can be used at most once.
+    pub fn show(&self, message: &str, undo: Option<std::boxed::Box<dyn Fn()>>) {
+        self.label.set_text(message);
+        *self.action.borrow_mut() = undo;
+        self.revealer.show_all();
+        self.undo.set_visible(self.action.borrow().is_some());
+        self.revealer.set_reveal_child(true);
+
+        // Only hide the toast if it hasn't been replaced by a newer one in the meantime.
+        let generation = self.generation.get().wrapping_add(1);
+        self.generation.set(generation);
+        let toast = self.clone();
+        glib::timeout_add_seconds_local(TOAST_SECONDS, move || {
+            if toast.generation.get() == generation {
+                toast.revealer.set_reveal_child(false);
+                toast.action.borrow_mut().take();
+            }
+            glib::Continue(false)
+        });
+    }
+}
@@ -0,0 +1,93 @@
+extern crate gtk;
+
+use crate::gui;
+use chrono::prelude::*;
+use gtk::prelude::*;
+use gtk::{Box, Button, Label, ListBox, Orientation, ScrolledWindow};
+use turbosql::select;
+
+#[derive(Debug, Clone)]
+struct DeletedSighting {
+    rowid: Option<i64>,
+    name: Option<String>,
+    seen_at: Option<i64>,
+    individuals: Option<i64>,
+}
+
+fn deleted_sightings() -> Vec<DeletedSighting> {
+    select!(Vec<DeletedSighting> "sighting.rowid, animal.name, seen_at, individuals from sighting left join animal on animal.rowid = sighting.animal_id where deleted_at is not null order by deleted_at desc, seen_at desc limit 200")
+        .unwrap_or_default()
+}
+
+fn format_time(timestamp: i64) -> String {
+    Local.timestamp(timestamp, 0).format("%a %e %b %Y %H:%M").to_string()
+}
+
+impl gui::Gui {
+
+    /// Builds the page listing cleared sightings so they can be restored.
+    pub fn build_trash(&self) -> gtk::Box {
+        let page = Box::new(Orientation::Vertical, 5);
+
+        let header = Box::new(Orientation::Horizontal, 5);
+        let back = Button::with_label("Back");
+        let stack = self.stack.clone();
+        back.connect_clicked(move |_| {
+            stack.set_visible_child_full("birds", gtk::StackTransitionType::SlideDown);
+        });
+        header.pack_start(&back, false, false, 0);
+        header.pack_start(&Label::new(Some("Deleted sightings")), true, true, 0);
+        page.pack_start(&header, false, false, 0);
+
+        let scroller = ScrolledWindow::new(gtk::NONE_ADJUSTMENT, gtk::NONE_ADJUSTMENT);
+        scroller.set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+        scroller.add(&self.trash);
+        page.pack_start(&scroller, true, true, 0);
+
+        page
+    }
+
+    pub fn show_trash(&self) {
+        self.populate_trash();
+        self.stack.set_visible_child_full("trash", gtk::StackTransitionType::SlideUp);
+    }
+
+    fn populate_trash(&self) {
+        let list: &ListBox = &self.trash;
+        list.foreach(|row| list.remove(row));
+
+        let sightings = deleted_sightings();
+        if sightings.is_empty() {
+            list.add(&Label::new(Some("The trash is empty.")));
+        }
+        for sighting in sightings {
+            let rowid = match sighting.rowid {
+                Some(rowid) => rowid,
+                None => continue,
+            };
+            let row = Box::new(Orientation::Horizontal, 10);
+            let text = format!(
+                "{} × {} — {}",
+                sighting.name.unwrap_or_else(|| String::from("Unknown species")),
+                sighting.individuals.unwrap_or(1),
+                format_time(sighting.seen_at.unwrap_or(0)),
+            );
+            let label = Label::new(Some(&text));
+            label.set_xalign(0.0);
+            row.pack_start(&label, true, true, 0);
+
+            let restore = Button::with_label("Restore");
+            let gui = self.clone();
+            restore.connect_clicked(move |_| {
+                crate::restore_sighting(rowid)
+                    .map_err(|e| gui::notices::notify(gui::notices::Severity::Error, &format!("Error restoring sighting - {}", e))).ok();
+                gui.populate_trash();
+                gui.chart.invalidate();
+                gui.refresh();
+            });
+            row.pack_end(&restore, false, false, 0);
+            list.add(&row);
+        }
+        list.show_all();
+    }
+}
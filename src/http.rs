@@ -0,0 +1,249 @@
+use crate::reports;
+use crate::Animal;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::Read;
+use std::thread;
+use tiny_http::{Header, Method, Request, Response, Server};
+use turbosql::select;
+
+#[derive(Debug, Clone)]
+struct SpeciesRow {
+    rowid: Option<i64>,
+    name: Option<String>,
+    scientific_name: Option<String>,
+    color: Option<String>,
+    today: Option<i64>,
+}
+
+/// A problem with what the client sent, as opposed to one on the server's side.
+#[derive(Debug)]
+struct BadRequest(String);
+
+impl fmt::Display for BadRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for BadRequest {}
+
+fn bad_request(error: impl ToString) -> Box<dyn Error> {
+    Box::new(BadRequest(error.to_string()))
+}
+
+/// The most a request body can be. A sighting needs a few dozen bytes.
+const MAX_BODY: u64 = 64 * 1024;
+
+/// Serves the JSON API on `port` from a background thread. `changed` is sent a message each
+/// time a sighting is logged through the API, so the GUI can redraw straight away.
+pub fn start(port: u16, changed: glib::Sender<()>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let server = Server::http(("0.0.0.0", port))?;
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            handle(request, &changed);
+        }
+    });
+    Ok(())
+}
+
+fn handle(mut request: Request, changed: &glib::Sender<()>) {
+    let url = request.url().to_string();
+    let (path, query) = match url.find('?') {
+        Some(i) => (&url[..i], parse_query(&url[i + 1..])),
+        None => (&url[..], HashMap::new()),
+    };
+
+    let method = request.method().clone();
+    let result = match (method, path) {
+        (Method::Get, "/species") => species(),
+        (Method::Get, "/sightings") => sightings(&query),
+        (Method::Post, "/sightings") => {
+            let mut body = String::new();
+            match request.as_reader().take(MAX_BODY + 1).read_to_string(&mut body) {
+                Ok(_) if body.len() as u64 > MAX_BODY => Ok((413, json!({ "error": "request body too large" }))),
+                Ok(_) => log(&body, changed),
+                Err(error) => Err(bad_request(error)),
+            }
+        }
+        (Method::Get, "/stats/weekly") => weekly(&query),
+        _ => Ok((404, json!({ "error": "not found" }))),
+    };
+    let (status, body) = result.unwrap_or_else(|error| {
+        let status = if error.is::<BadRequest>() { 400 } else { 500 };
+        (status, json!({ "error": error.to_string() }))
+    });
+
+    let response = Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap());
+    request.respond(response)
+        .map_err(|error| println!("Error responding to HTTP request.\nError: {}", error)).ok();
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query.split('&')
+        .filter_map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            Some((decode(parts.next()?), decode(parts.next().unwrap_or(""))))
+        })
+        .collect()
+}
+
+/// Decodes a form-encoded query value, where `+` is a space and `%XX` a byte.
+fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => match value.get(i + 1..i + 3)
+                .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Builds a report filter from `from`/`to` dates (`to` inclusive) and `site_id`/`observer_id` parameters.
+fn filter(query: &HashMap<String, String>) -> Result<reports::Filter, Box<dyn Error>> {
+    let mut filter = reports::Filter::default();
+    if let Some(date) = query.get("from") {
+        filter.from = reports::parse_date(date).map_err(bad_request)?.timestamp();
+    }
+    if let Some(date) = query.get("to") {
+        filter.to = (reports::parse_date(date).map_err(bad_request)? + chrono::Duration::days(1)).timestamp();
+    }
+    if let Some(id) = query.get("site_id") {
+        filter.site_id = Some(id.parse::<i64>().map_err(|_| bad_request(format!("\"{}\" is not a site id", id)))?);
+    }
+    if let Some(id) = query.get("observer_id") {
+        filter.observer_id = Some(id.parse::<i64>().map_err(|_| bad_request(format!("\"{}\" is not an observer id", id)))?);
+    }
+    Ok(filter)
+}
+
+fn species() -> Result<(u16, Value), Box<dyn Error>> {
+    let rows = select!(Vec<SpeciesRow> r#"animal.rowid, animal.name, animal.scientific_name, animal.color, (select coalesce(sum(coalesce(individuals, 1)), 0) from sighting where sighting.animal_id = animal.rowid and deleted_at is null and date(seen_at, "unixepoch", "localtime") = date("now", "localtime")) as today from animal order by animal.name"#)?;
+    let species: Vec<Value> = rows.into_iter().map(|row| json!({
+        "id": row.rowid,
+        "name": row.name,
+        "scientific_name": row.scientific_name,
+        "color": row.color,
+        "seen_today": row.today.unwrap_or(0),
+    })).collect();
+    Ok((200, json!(species)))
+}
+
+fn sightings(query: &HashMap<String, String>) -> Result<(u16, Value), Box<dyn Error>> {
+    let rows = reports::sightings(&filter(query)?)?;
+    let sightings: Vec<Value> = rows.into_iter().map(|row| json!({
+        "id": row.rowid,
+        "seen_at": row.seen_at,
+        "species": row.species,
+        "individuals": row.individuals,
+        "site": row.site,
+        "observer": row.observer,
+    })).collect();
+    Ok((200, json!(sightings)))
+}
+
+fn weekly(query: &HashMap<String, String>) -> Result<(u16, Value), Box<dyn Error>> {
+    let rows = reports::weekly(&filter(query)?)?;
+    let weeks: Vec<Value> = rows.into_iter().map(|row| json!({
+        "week": row.week,
+        "species": row.species,
+        "color": row.color,
+        "days": row.days,
+        "individuals": row.individuals,
+    })).collect();
+    Ok((200, json!(weeks)))
+}
+
+/// Logs a sighting from a body like `{"species": "Galah", "individuals": 12}`, where the
+/// species can be given by name, species code or `species_id`.
+fn log(body: &str, changed: &glib::Sender<()>) -> Result<(u16, Value), Box<dyn Error>> {
+    let request: Value = serde_json::from_str(body).map_err(bad_request)?;
+    let animal = match (request["species_id"].as_i64(), request["species"].as_str()) {
+        (Some(animal_id), _) => select!(Animal "where rowid = ?", animal_id)
+            .map_err(|_| bad_request(format!("no species with id {}", animal_id)))?,
+        (None, Some(name)) => reports::find_animal(name).map_err(bad_request)?,
+        (None, None) => return Err(bad_request("a species or species_id is needed")),
+    };
+    let animal_id = reports::loggable(animal).map_err(bad_request)?.rowid.unwrap_or(0);
+    let individuals = match &request["individuals"] {
+        Value::Null => 1,
+        value => value.as_i64().ok_or_else(|| bad_request("individuals must be a whole number"))?,
+    };
+    if individuals < 1 {
+        return Err(bad_request("individuals must be at least 1"));
+    }
+
+    let rowid = crate::log_sighting(animal_id, individuals)?;
+    changed.send(()).ok();
+    Ok((201, json!({ "id": rowid })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn decode_spaces_and_escapes() {
+        assert_eq!(decode("Rainbow+Lorikeet"), "Rainbow Lorikeet");
+        assert_eq!(decode("Pond%20%26%20Creek"), "Pond & Creek");
+        assert_eq!(decode("caf%C3%A9"), "café");
+        assert_eq!(decode("%2b%2B"), "++");
+    }
+
+    #[test]
+    fn decode_leaves_malformed_escapes() {
+        assert_eq!(decode("100%"), "100%");
+        assert_eq!(decode("%4"), "%4");
+        assert_eq!(decode("%zz"), "%zz");
+        assert_eq!(decode("%+1"), "% 1");
+        assert_eq!(decode("%%41"), "%A");
+        assert_eq!(decode("%é"), "%é");
+    }
+
+    #[test]
+    fn parse_query_decodes_keys_and_values() {
+        let parsed = parse_query("species=Rainbow+Lorikeet&site%5Fid=2&empty");
+        assert_eq!(parsed.get("species").map(String::as_str), Some("Rainbow Lorikeet"));
+        assert_eq!(parsed.get("site_id").map(String::as_str), Some("2"));
+        assert_eq!(parsed.get("empty").map(String::as_str), Some(""));
+    }
+
+    #[test]
+    fn filter_reads_dates_and_ids() {
+        let filter = filter(&query(&[("from", "2021-01-01"), ("to", "2021-01-31"), ("site_id", "2")])).unwrap();
+        assert_eq!(filter.from, reports::parse_date("2021-01-01").unwrap().timestamp());
+        // `to` takes in the whole of that day.
+        assert_eq!(filter.to, reports::parse_date("2021-02-01").unwrap().timestamp());
+        assert_eq!(filter.site_id, Some(2));
+        assert_eq!(filter.observer_id, None);
+    }
+
+    #[test]
+    fn filter_rejects_bad_values_as_bad_requests() {
+        for pair in &[("from", "1/1/2021"), ("to", "2021-02-30"), ("site_id", "Pond"), ("observer_id", "1.5")] {
+            let error = filter(&query(&[*pair])).unwrap_err();
+            assert!(error.is::<BadRequest>(), "{:?} gave {}", pair, error);
+        }
+    }
+}
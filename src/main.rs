@@ -4,26 +4,66 @@ extern crate gio;
 extern crate glib;
 extern crate gtk;
 
+mod checklist;
+mod cli;
+mod colors;
 mod gui;
+mod http;
+mod mqtt;
+mod reports;
 
 use gio::prelude::*;
 use gtk::prelude::*;
 pub use gui::*;
 pub use gui::images::*;
 pub use gui::charts::*;
+use std::convert::TryFrom;
 use std::env::args;
+use std::error::Error;
 use std::result::Result;
-use turbosql::{Blob, Turbosql, execute};
+use turbosql::{Blob, Turbosql, execute, select};
 
 use chrono::Local;
 use gtk::{Application, ApplicationWindow};
 
+pub const APP_ID: &str = "com.github.ggtrigg.bird_counter";
+
 #[derive(Turbosql, Default)]
 pub struct Animal {
     rowid: Option<i64>,
     name: Option<String>,
     filename: Option<String>,
     image: Option<Blob>,
+    scientific_name: Option<String>,
+    family: Option<String>,
+    taxon_order: Option<String>,
+    species_code: Option<String>,
+    archived_at: Option<i64>,
+    position: Option<i64>,
+    color: Option<String>,
+}
+
+/// A species from an imported IOC/Clements-style checklist.
+#[derive(Turbosql, Default)]
+pub struct Taxon {
+    rowid: Option<i64>,
+    common_name: Option<String>,
+    scientific_name: Option<String>,
+    family: Option<String>,
+    taxon_order: Option<String>,
+    species_code: Option<String>,
+}
+
+#[derive(Turbosql, Default)]
+pub struct Site {
+    rowid: Option<i64>,
+    name: Option<String>,
+}
+
+#[derive(Turbosql, Default)]
+pub struct Observer {
+    rowid: Option<i64>,
+    name: Option<String>,
 }
 
 #[derive(Turbosql, Default)]
@@ -31,22 +71,42 @@ struct Sighting {
     rowid: Option<i64>,
     animal_id: Option<i64>,
     seen_at: Option<i64>,
+    individuals: Option<i64>,
+    site_id: Option<i64>,
+    observer_id: Option<i64>,
+    deleted_at: Option<i64>,
+}
+
+#[derive(Turbosql, Default)]
+struct Setting {
+    rowid: Option<i64>,
+    name: Option<String>,
+    value: Option<String>,
 }
 
 impl Sighting {
-    fn new(animal_id: i64) -> Sighting {
+    fn new(animal_id: i64, individuals: i64) -> Sighting {
         let s = Sighting {
             rowid: None,
             animal_id: Some(animal_id),
             seen_at: Some(Local::now().timestamp()),
+            individuals: Some(individuals),
+            site_id: active_site(),
+            observer_id: active_observer(),
+            deleted_at: None,
         };
         s
     }
 }
 
 fn main() {
+    let args: Vec<String> = args().collect();
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
+
     let application =
-        Application::new(Some("com.github.ggtrigg.bird_counter"), Default::default())
+        Application::new(Some(APP_ID), Default::default())
             .expect("failed to initialize GTK application");
 
     application.add_main_option(
@@ -57,11 +117,61 @@ fn main() {
         "start in fullscreen mode",
         None,
     );
+    application.add_main_option(
+        "import-checklist",
+        glib::Char::new('i').unwrap(),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::String,
+        "import a species checklist from a CSV file and exit",
+        Some("FILE"),
+    );
+    application.add_main_option(
+        "http-port",
+        glib::Char::new('p').unwrap(),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::Int,
+        "serve the JSON API for other devices on the local network on this port",
+        Some("PORT"),
+    );
+    application.add_main_option(
+        "mqtt-broker",
+        glib::Char::new('m').unwrap(),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::String,
+        "publish sightings to this MQTT broker from now on (\"off\" to stop)",
+        Some("HOST[:PORT]"),
+    );
+    application.add_main_option(
+        "grid",
+        glib::Char::new('g').unwrap(),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::String,
+        "lay species out in a grid this size from now on (\"auto\" to fit the window)",
+        Some("COLUMNSxROWS"),
+    );
+    application.add_main_option(
+        "action",
+        glib::Char::new('a').unwrap(),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::String,
+        "run an action (e.g. log-sighting=Galah, show-charts) in the running instance and exit",
+        Some("NAME[=SPECIES]"),
+    );
     application.connect_handle_local_options(handle_local_options);
 
     application.connect_activate(|app| {
-        let animals: Vec<Animal> =
-            turbosql::select!(Vec<Animal>).expect("Couldn't retrieve animals");
+        // A second launch activates the running instance, which already has a window.
+        if let Some(window) = app.get_active_window() {
+            window.present();
+            return;
+        }
+        gui::images::store_missing_images();
+        // Species added before species had colours get theirs before anything draws them.
+        if let Err(error) = colors::assign_missing() {
+            gui::notices::notify(gui::notices::Severity::Error, &format!("Error giving species their chart colours - {}", error));
+        }
+        mqtt::start();
+        mqtt::announce();
         let is_fullscreen: &bool = unsafe { app.get_data("fullscreen").unwrap_or(&false) };
 
         let window = ApplicationWindow::new(app);
@@ -72,38 +182,185 @@ fn main() {
             window.fullscreen();
         }
         let gui = gui::Gui::new(window);
-        gui.window.add(&gui.build(animals));
+        gui.window.add(&gui.build());
+        gui.add_actions(app);
         let mainwin = gui.window.clone();
-        
+
+        if let Some(port) = unsafe { app.get_data::<i32>("http_port") } {
+            let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+            let started = u16::try_from(*port)
+                .map_err(|_| format!("{} is not a port number", port).into())
+                .and_then(|port| http::start(port, sender));
+            match started {
+                Ok(()) => {
+                    let gui = gui.clone();
+                    receiver.attach(None, move |_| {
+                        gui.refresh();
+                        glib::Continue(true)
+                    });
+                }
+                Err(error) => println!("Error starting HTTP server on port {}.\nError: {}", port, error),
+            }
+        }
+
         glib::timeout_add_seconds_local(300, move || {
             gui.refresh();
+            mqtt::publish_states();
             glib::Continue(true)
         });
 
         mainwin.show_all();
     });
 
-    let args: Vec<String> = args().collect();
     application.run(&args);
 }
 
-fn log_sighting(animal_id: i64) {
-    let s = Sighting::new(animal_id);
-    if let Err(error) = s.insert() {
-        gui::alert(&format!("Error logging sighting - {}", error));
+pub fn log_sighting(animal_id: i64, individuals: i64) -> Result<i64, Box<dyn Error>> {
+    let s = Sighting::new(animal_id, individuals);
+    let rowid = s.insert()?;
+    mqtt::publish_event(animal_id, "logged", individuals);
+    Ok(rowid)
+}
+
+/// Moves today's sightings of a species (at the active site, by the active observer) to the
+/// trash, returning their rowids so the clear can be undone with `restore_cleared`.
+pub fn clear_sighting(animal_id: i64) -> Result<Vec<i64>, Box<dyn Error>> {
+    let site_id = active_site();
+    let observer_id = active_observer();
+    let cleared: Vec<i64> = select!(Vec<Sighting> r#"WHERE animal_id = ? AND deleted_at IS NULL AND (? IS NULL OR site_id = ?) AND (? IS NULL OR observer_id = ?) AND date(seen_at, "unixepoch", "localtime") = date("now", "localtime")"#,
+        animal_id, site_id, site_id, observer_id, observer_id)?
+        .iter()
+        .filter_map(|sighting| sighting.rowid)
+        .collect();
+    let deleted_at = Local::now().timestamp();
+    execute!("BEGIN")?;
+    for rowid in &cleared {
+        if let Err(error) = execute!("UPDATE sighting SET deleted_at = ? WHERE rowid = ?", deleted_at, rowid) {
+            execute!("ROLLBACK").ok();
+            return Err(error.into());
+        }
+    }
+    execute!("COMMIT")?;
+    mqtt::publish_event(animal_id, "cleared", 0);
+    Ok(cleared)
+}
+
+/// Takes the sightings a `clear_sighting` moved to the trash back out again.
+pub fn restore_cleared(animal_id: i64, cleared: &[i64]) -> Result<(), Box<dyn Error>> {
+    for rowid in cleared {
+        execute!("UPDATE sighting SET deleted_at = NULL WHERE rowid = ?", rowid)?;
+    }
+    mqtt::publish_state(animal_id);
+    Ok(())
+}
+
+/// Removes a sighting that was logged by mistake. Unlike clearing, it isn't kept in the trash.
+pub fn undo_sighting(rowid: i64) -> Result<(), Box<dyn Error>> {
+    let animal_id = select!(i64 "animal_id from sighting where rowid = ?", rowid)?;
+    execute!("DELETE FROM sighting WHERE rowid = ?", rowid)?;
+    mqtt::publish_state(animal_id);
+    Ok(())
+}
+
+pub fn restore_sighting(rowid: i64) -> Result<(), Box<dyn Error>> {
+    execute!("UPDATE sighting SET deleted_at = NULL WHERE rowid = ?", rowid)?;
+    sighting_changed(rowid);
+    Ok(())
+}
+
+fn sighting_changed(rowid: i64) {
+    if let Ok(animal_id) = select!(i64 "animal_id from sighting where rowid = ?", rowid) {
+        mqtt::publish_state(animal_id);
     }
 }
 
-pub fn clear_sighting(animal_id: i64) {
-    execute!(r#"DELETE FROM sighting WHERE animal_id = ? AND date(seen_at, "unixepoch", "localtime") = date("now", "localtime")"#, animal_id).ok();
+pub fn get_setting(name: &str) -> Option<String> {
+    select!(Setting "where name = ?", name).ok().and_then(|setting| setting.value)
+}
+
+pub fn delete_setting(name: &str) -> Result<(), Box<dyn Error>> {
+    execute!("DELETE FROM setting WHERE name = ?", name)?;
+    Ok(())
+}
+
+pub fn set_setting(name: &str, value: &str) -> Result<(), Box<dyn Error>> {
+    match select!(Setting "where name = ?", name) {
+        Ok(_) => execute!("UPDATE setting SET value = ? WHERE name = ?", value, name).map(|_| ())?,
+        Err(_) => Setting {
+            rowid: None,
+            name: Some(name.to_string()),
+            value: Some(value.to_string()),
+        }.insert().map(|_| ())?,
+    };
+    Ok(())
+}
+
+/// The site new sightings are logged against, if one has been picked.
+pub fn active_site() -> Option<i64> {
+    get_setting("active_site").and_then(|value| value.parse::<i64>().ok())
+}
+
+/// The person new sightings are credited to, if one has been picked.
+pub fn active_observer() -> Option<i64> {
+    get_setting("active_observer").and_then(|value| value.parse::<i64>().ok())
+}
+
+/// Asks an already running instance to run one of its actions, returning whether there was one to ask.
+pub fn activate_remote_action(app: &impl IsA<gio::Application>, name: &str, param: Option<&glib::Variant>) -> bool {
+    if app.register(gio::NONE_CANCELLABLE).is_err() || !app.get_is_remote() {
+        return false;
+    }
+    app.upcast_ref::<gio::Application>().activate_action(name, param);
+    // The call is only queued, so send it before this process exits.
+    if let Some(connection) = app.get_dbus_connection() {
+        connection.flush_sync(gio::NONE_CANCELLABLE)
+            .map_err(|error| eprintln!("Error sending {} to the running instance.\nError: {}", name, error)).ok();
+    }
+    true
 }
 
 fn handle_local_options(app: &gtk::Application, opts: &glib::VariantDict) -> i32 {
+    if let Some(action) = opts.lookup_value("action", None).and_then(|v| v.get::<String>()) {
+        let (name, param) = match action.find('=') {
+            Some(i) => (&action[..i], Some(action[i + 1..].to_variant())),
+            None => (&action[..], None),
+        };
+        if activate_remote_action(app, name, param.as_ref()) {
+            return 0;
+        }
+        eprintln!("Bird Counter isn't running, so can't run {}", name);
+        return 1;
+    }
+    if let Some(path) = opts.lookup_value("import-checklist", None).and_then(|v| v.get::<String>()) {
+        return match checklist::import(std::path::Path::new(&path)) {
+            Ok(count) => {
+                println!("Imported {} species from {}", count, path);
+                0
+            }
+            Err(error) => {
+                eprintln!("Error importing checklist {}: {}", path, error);
+                1
+            }
+        };
+    }
+    if let Some(broker) = opts.lookup_value("mqtt-broker", None).and_then(|v| v.get::<String>()) {
+        let broker = if broker == "off" { String::new() } else { broker };
+        if let Err(error) = set_setting("mqtt_broker", &broker) {
+            eprintln!("Error saving MQTT broker: {}", error);
+            return 1;
+        }
+    }
+    if let Some(grid) = opts.lookup_value("grid", None).and_then(|v| v.get::<String>()) {
+        let grid = if grid == "auto" { String::new() } else { grid };
+        let checked = if grid == "" { Ok(()) } else { gui::images::parse_grid(&grid).map(|_| ()) };
+        if let Err(error) = checked.map_err(|e| e.into()).and_then(|_| set_setting("grid", &grid)) {
+            eprintln!("Error saving grid size: {}", error);
+            return 1;
+        }
+    }
     unsafe { app.set_data("fullscreen", opts.contains("fullscreen")) }
+    if let Some(port) = opts.lookup_value("http-port", None).and_then(|v| v.get::<i32>()) {
+        unsafe { app.set_data("http_port", port) }
+    }
     -1
 }
-
-// To get daily bird sighting data from db...
-// select distinct animal.name as name, date(seen_at, "unixepoch", "localtime") as date from sighting left join animal on animal.rowid = sighting.animal_id order by name,date;
-// To get a weekly breakdown...
-// select animal.name, count(distinct date(seen_at, "unixepoch", "localtime")), strftime("%Y%W", seen_at, "unixepoch", "localtime") as week from sighting left join animal on animal.rowid = sighting.animal_id group by animal_id,week;
\ No newline at end of file
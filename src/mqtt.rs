@@ -0,0 +1,203 @@
+use crate::gui::notices::{log, Severity};
+use crate::Animal;
+use once_cell::sync::OnceCell;
+use rumqttc::{Client, Event, MqttOptions, Outgoing, Packet, QoS};
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use turbosql::select;
+
+const DEFAULT_PORT: u16 = 1883;
+const TOPIC: &str = "bird_counter";
+const DISCOVERY_TOPIC: &str = "homeassistant";
+/// How long to wait before reconnecting after an error.
+const RETRY_DELAY: Duration = Duration::from_secs(10);
+/// How long `stop` waits for queued messages to be sent, so an unreachable broker doesn't hold
+/// up the command line.
+const STOP_TIMEOUT: Duration = Duration::from_secs(2);
+/// Each species' Home Assistant sensors, as (key, label, unit).
+const SENSORS: [(&str, &str, &str); 2] = [
+    ("seen_today", "seen today", "birds"),
+    ("days_this_week", "days this week", "days"),
+];
+
+struct Publisher {
+    client: Client,
+    /// Hears from the connection's thread when it has finished.
+    finished: Mutex<Option<Receiver<()>>>,
+    stopping: AtomicBool,
+}
+
+static PUBLISHER: OnceCell<Publisher> = OnceCell::new();
+
+/// Connects to the broker saved in the `mqtt_broker` setting, if there is one.
+pub fn start() {
+    if PUBLISHER.get().is_some() {
+        return;
+    }
+    let broker = match crate::get_setting("mqtt_broker") {
+        Some(broker) if broker != "" => broker,
+        _ => return,
+    };
+    let (host, port) = match broker.rfind(':') {
+        Some(i) => (broker[..i].to_string(), broker[i + 1..].parse::<u16>().unwrap_or(DEFAULT_PORT)),
+        None => (broker.clone(), DEFAULT_PORT),
+    };
+    let mut options = MqttOptions::new(format!("{}-{}", TOPIC, std::process::id()), host, port);
+    options.set_keep_alive(Duration::from_secs(30));
+    let (client, mut connection) = Client::new(options, 1000);
+
+    let publisher = PUBLISHER.get_or_init(|| Publisher {
+        client,
+        finished: Mutex::new(None),
+        stopping: AtomicBool::new(false),
+    });
+    let (finished, on_finished) = mpsc::channel();
+    thread::spawn(move || {
+        // The connection has to be polled for anything to be sent; it reconnects by itself.
+        // The same error over and over while the broker is down is only logged once.
+        let mut last_error = None;
+        for notification in connection.iter() {
+            match notification {
+                Ok(Event::Outgoing(Outgoing::Disconnect)) => break,
+                Ok(Event::Incoming(Packet::ConnAck(_))) => last_error = None,
+                Ok(_) => (),
+                Err(error) => {
+                    if stopping() {
+                        break;
+                    }
+                    let message = format!("Error talking to MQTT broker {} - {}", broker, error);
+                    if last_error.as_ref() != Some(&message) {
+                        log(Severity::Warning, &message);
+                        last_error = Some(message);
+                    }
+                    let retry_at = Instant::now() + RETRY_DELAY;
+                    while Instant::now() < retry_at && !stopping() {
+                        thread::sleep(Duration::from_millis(100));
+                    }
+                    if stopping() {
+                        break;
+                    }
+                }
+            }
+        }
+        finished.send(()).ok();
+    });
+    *publisher.finished.lock().unwrap() = Some(on_finished);
+}
+
+fn stopping() -> bool {
+    PUBLISHER.get().map(|p| p.stopping.load(Ordering::SeqCst)).unwrap_or(true)
+}
+
+/// Publishes Home Assistant discovery config and current totals for every species.
+pub fn announce() {
+    if PUBLISHER.get().is_none() {
+        return;
+    }
+    for animal in select!(Vec<Animal>).unwrap_or_default() {
+        if let Some(rowid) = animal.rowid {
+            publish_discovery(rowid);
+            publish_state(rowid);
+        }
+    }
+}
+
+/// Disconnects once everything queued has been sent, or `STOP_TIMEOUT` has passed. Short-lived
+/// processes (the command line) call this before exiting so their messages aren't lost.
+pub fn stop() {
+    if let Some(publisher) = PUBLISHER.get() {
+        publisher.stopping.store(true, Ordering::SeqCst);
+        publisher.client.clone().try_disconnect().ok();
+        if let Some(finished) = publisher.finished.lock().unwrap().take() {
+            finished.recv_timeout(STOP_TIMEOUT).ok();
+        }
+    }
+}
+
+fn publish(topic: String, retain: bool, payload: String) {
+    if let Some(publisher) = PUBLISHER.get() {
+        publisher.client.clone().try_publish(topic, QoS::AtLeastOnce, retain, payload)
+            .map_err(|error| log(Severity::Warning, &format!("Error publishing to MQTT - {}", error))).ok();
+    }
+}
+
+/// Announces a species' sensors to Home Assistant.
+pub fn publish_discovery(animal_id: i64) {
+    if PUBLISHER.get().is_none() {
+        return;
+    }
+    let name = match select!(Animal "where rowid = ?", animal_id).ok().and_then(|animal| animal.name) {
+        Some(name) => name,
+        None => return,
+    };
+    for (key, label, unit) in SENSORS.iter() {
+        let unique_id = format!("{}_{}_{}", TOPIC, animal_id, key);
+        let config = json!({
+            "name": format!("{} {}", name, label),
+            "unique_id": unique_id,
+            "state_topic": format!("{}/{}/state", TOPIC, animal_id),
+            "value_template": format!("{{{{ value_json.{} }}}}", key),
+            "unit_of_measurement": unit,
+            "icon": "mdi:bird",
+            "device": {
+                "identifiers": [TOPIC],
+                "name": "Bird Counter",
+            },
+        });
+        publish(format!("{}/sensor/{}/config", DISCOVERY_TOPIC, unique_id), true, config.to_string());
+    }
+}
+
+/// Removes a deleted species' sensors from Home Assistant.
+pub fn remove_discovery(animal_id: i64) {
+    for (key, _, _) in SENSORS.iter() {
+        let unique_id = format!("{}_{}_{}", TOPIC, animal_id, key);
+        publish(format!("{}/sensor/{}/config", DISCOVERY_TOPIC, unique_id), true, String::new());
+    }
+    publish(format!("{}/{}/state", TOPIC, animal_id), true, String::new());
+}
+
+/// Publishes a species' current totals, which Home Assistant reads its sensor values from.
+pub fn publish_state(animal_id: i64) {
+    if PUBLISHER.get().is_none() {
+        return;
+    }
+    let seen_today = select!(i64 r#"coalesce(sum(coalesce(individuals, 1)), 0) from sighting where animal_id = ? and deleted_at is null and date(seen_at, "unixepoch", "localtime") = date("now", "localtime")"#, animal_id).unwrap_or(0);
+    let days_this_week = select!(i64 r#"count(distinct date(seen_at, "unixepoch", "localtime")) from sighting where animal_id = ? and deleted_at is null and date(seen_at, "unixepoch", "localtime", "weekday 0", "-6 days") = date("now", "localtime", "weekday 0", "-6 days")"#, animal_id).unwrap_or(0);
+    let state = json!({
+        "seen_today": seen_today,
+        "days_this_week": days_this_week,
+    });
+    publish(format!("{}/{}/state", TOPIC, animal_id), true, state.to_string());
+}
+
+/// Republishes every species' totals, so "seen today" and "days this week" go back to zero
+/// when the day or week rolls over even if nothing is logged.
+pub fn publish_states() {
+    if PUBLISHER.get().is_none() {
+        return;
+    }
+    for animal in select!(Vec<Animal>).unwrap_or_default() {
+        if let Some(rowid) = animal.rowid {
+            publish_state(rowid);
+        }
+    }
+}
+
+/// Publishes that a species was logged or cleared, followed by its updated totals.
+pub fn publish_event(animal_id: i64, event: &str, individuals: i64) {
+    if PUBLISHER.get().is_none() {
+        return;
+    }
+    let message = json!({
+        "event": event,
+        "individuals": individuals,
+        "at": chrono::Local::now().to_rfc3339(),
+    });
+    publish(format!("{}/{}/event", TOPIC, animal_id), false, message.to_string());
+    publish_state(animal_id);
+}
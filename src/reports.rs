@@ -0,0 +1,82 @@
+use crate::Animal;
+use chrono::prelude::*;
+use std::error::Error;
+use turbosql::select;
+
+/// Which sightings a report covers: `from`..`to` are unix timestamps (end exclusive), and a
+/// site or observer of `None` includes everyone's sightings everywhere.
+#[derive(Debug, Clone, Copy)]
+pub struct Filter {
+    pub from: i64,
+    pub to: i64,
+    pub site_id: Option<i64>,
+    pub observer_id: Option<i64>,
+}
+
+impl Default for Filter {
+    fn default() -> Filter {
+        Filter {
+            from: 0,
+            to: i64::MAX,
+            site_id: None,
+            observer_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SightingRow {
+    pub rowid: Option<i64>,
+    pub seen_at: Option<i64>,
+    pub species: Option<String>,
+    pub individuals: Option<i64>,
+    pub site: Option<String>,
+    pub observer: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WeeklyRow {
+    pub week: Option<String>,
+    pub species: Option<String>,
+    /// The species' colour in charts, as `#rrggbb`.
+    pub color: Option<String>,
+    pub days: Option<i64>,
+    pub individuals: Option<i64>,
+}
+
+/// Individual sightings, oldest first.
+pub fn sightings(filter: &Filter) -> Result<Vec<SightingRow>, Box<dyn Error>> {
+    let Filter { from, to, site_id, observer_id } = *filter;
+    Ok(select!(Vec<SightingRow> "sighting.rowid, sighting.seen_at, animal.name as species, coalesce(sighting.individuals, 1) as individuals, site.name as site, observer.name as observer from sighting left join animal on animal.rowid = sighting.animal_id left join site on site.rowid = sighting.site_id left join observer on observer.rowid = sighting.observer_id where sighting.deleted_at is null and sighting.seen_at >= ? and sighting.seen_at < ? and (? is null or sighting.site_id = ?) and (? is null or sighting.observer_id = ?) order by sighting.seen_at",
+        from, to, site_id, site_id, observer_id, observer_id)?)
+}
+
+/// Days seen and individuals counted per species per calendar week, each week given by the
+/// date of its Monday.
+pub fn weekly(filter: &Filter) -> Result<Vec<WeeklyRow>, Box<dyn Error>> {
+    let Filter { from, to, site_id, observer_id } = *filter;
+    Ok(select!(Vec<WeeklyRow> r#"date(seen_at, "unixepoch", "localtime", "weekday 0", "-6 days") as week, animal.name as species, animal.color as color, count(distinct date(seen_at, "unixepoch", "localtime")) as days, sum(coalesce(individuals, 1)) as individuals from sighting left join animal on animal.rowid = sighting.animal_id where deleted_at is null and seen_at >= ? and seen_at < ? and (? is null or site_id = ?) and (? is null or observer_id = ?) group by week, animal_id order by week, species"#,
+        from, to, site_id, site_id, observer_id, observer_id)?)
+}
+
+/// Looks a species up by its name or species code, ignoring case.
+pub fn find_animal(name: &str) -> Result<Animal, Box<dyn Error>> {
+    select!(Animal "where name = ? collate nocase or species_code = ? collate nocase", name, name)
+        .map_err(|_| format!("no species called \"{}\"", name).into())
+}
+
+/// Passes a species through unless it's archived, as archived species can't be logged.
+pub fn loggable(animal: Animal) -> Result<Animal, Box<dyn Error>> {
+    match animal.archived_at {
+        Some(_) => Err(format!("{} is archived, unarchive it to log it again", animal.name.unwrap_or_default()).into()),
+        None => Ok(animal),
+    }
+}
+
+/// Parses a `YYYY-MM-DD` date as the start of that day in local time.
+pub fn parse_date(date: &str) -> Result<DateTime<Local>, Box<dyn Error>> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("\"{}\" is not a YYYY-MM-DD date", date))?;
+    Local.from_local_datetime(&date.and_hms(0, 0, 0)).earliest()
+        .ok_or_else(|| format!("{} has no midnight in the local time zone", date).into())
+}